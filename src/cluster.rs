@@ -0,0 +1,2788 @@
+//! Greedy search for clusters (subfactories) in the item dependency graph.
+//!
+//! An edge `a -> b` means that `b` is a dependency of `a`, i.e., `b` is required to produce `a`.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, HashMap, HashSet, VecDeque};
+use std::collections::hash_map::Entry;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use log::*;
+use petgraph::Direction;
+use petgraph::graph::EdgeReference;
+use petgraph::visit::EdgeRef;
+use serde::Deserialize;
+
+use crate::factorio;
+use crate::graphviz::{self, AttrValue, DotGraph, DotGraphBuilder, Edge, EdgeIndex, GraphType, Node, NodeIndex, NotADag, Subgraph};
+
+/// Score of a cluster as used by the greedy search. Lower is better.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ClusterScore {
+    /// Number of dependencies, i.e., number of components required as input.
+    pub num_deps: usize,
+    /// Number of distinct output products required by other components.
+    pub num_outputs: usize,
+}
+
+impl ClusterScore {
+    /// Sum of inputs and outputs, which the greedy search tries to minimize with the default
+    /// weights.
+    pub fn total(&self) -> usize {
+        self.num_deps + self.num_outputs
+    }
+
+    /// Sum of inputs and outputs weighted by `params.dep_weight` and `params.output_weight`,
+    /// which the greedy search tries to minimize.
+    pub fn weighted(&self, params: &ClusterParams) -> f64 {
+        params.dep_weight * self.num_deps as f64 + params.output_weight * self.num_outputs as f64
+    }
+}
+
+/// Calculates the score of the given subgraph.
+///
+/// An item of the subgraph is an output if it's consumed outside of the subgraph and at least one
+/// of its dependencies is produced inside of the subgraph.
+/// Whether the item is also consumed inside of the subgraph doesn't matter, i.e., a final product
+/// of the subgraph consumed only by other components is an output.
+/// Items whose dependencies are all external (including raw resources) only pass through the
+/// subgraph and aren't counted.
+///
+/// Dependencies of an item whose edges share an `alternative_group` attribute are substitutable
+/// ingredients and count as a single dependency, or not at all if any of them is part of the
+/// subgraph.
+pub fn score(subgraph: &HashSet<NodeIndex>, graph: &DotGraph) -> ClusterScore {
+    score_with_params(subgraph, graph, &ClusterParams::default())
+}
+
+/// Calculates the score of the given subgraph like [`score`](fn.score.html), taking
+/// `params.dir_hints`, `params.undirected` and `params.model` into account.
+pub fn score_with_params(subgraph: &HashSet<NodeIndex>, graph: &DotGraph, params: &ClusterParams) -> ClusterScore {
+    if params.model == GraphModel::Bipartite {
+        return score_bipartite(subgraph, graph);
+    }
+    let neighbors = |node_idx, direction| match params.undirected {
+        true => hinted_edges(graph, node_idx, Direction::Outgoing, false).into_iter()
+            .chain(hinted_edges(graph, node_idx, Direction::Incoming, false))
+            .collect::<Vec<_>>().into_iter(),
+        false => hinted_edges(graph, node_idx, direction, params.dir_hints).into_iter(),
+    };
+
+    // number of dependencies, i.e., number of components required as input
+    let num_deps = subgraph.iter()
+        .copied()
+        .map(|node_idx| {
+            // alternative ingredients count once, unless any of them is produced internally
+            let mut alternatives: HashMap<&str, bool> = HashMap::new();
+            let mut count = 0;
+            for (neighbor_idx, edge_idx) in neighbors(node_idx, Direction::Outgoing) {
+                match graph[edge_idx].attributes.get("alternative_group") {
+                    Some(group) => *alternatives.entry(group).or_insert(false) |= subgraph.contains(&neighbor_idx),
+                    None if !subgraph.contains(&neighbor_idx) => count += 1,
+                    None => (),
+                }
+            }
+            count + alternatives.values().filter(|&&internal| !internal).count()
+        }).sum();
+
+    // Number of outputs needed by other components,
+    // i.e. number of distinct output products required by other components.
+    // However, we shouldn't count sole inputs as output components (e.g. don't pipe through iron-plates).
+    // Note that the second filter checks for an internal *dependency*, not an internal consumer.
+    let num_outputs = subgraph.iter()
+        .copied()
+        .filter(|node_idx|
+            neighbors(*node_idx, Direction::Incoming)
+                .any(|(neighbor_idx, _)| !subgraph.contains(&neighbor_idx))
+        ).filter(|node_ix|
+            neighbors(*node_ix, Direction::Outgoing)
+                .any(|(neighbor_ix, _)| subgraph.contains(&neighbor_ix))
+        ).count();
+    ClusterScore { num_deps, num_outputs }
+}
+
+/// Calculates the score of the recipes of the subgraph in a bipartite graph, see
+/// [`GraphModel::Bipartite`](enum.GraphModel.html#variant.Bipartite).
+///
+/// Item nodes aren't members of the subgraph, but its inputs and outputs: each item consumed by
+/// a recipe of the subgraph, but not produced by any of them, is a dependency, and each item
+/// produced by a recipe of the subgraph and consumed by a recipe outside of it is an output.
+fn score_bipartite(subgraph: &HashSet<NodeIndex>, graph: &DotGraph) -> ClusterScore {
+    let recipes: Vec<_> = subgraph.iter().copied().filter(|&node_idx| !is_item(graph, node_idx)).collect();
+    let consumed: HashSet<_> = recipes.iter()
+        .flat_map(|&recipe_idx| graph.neighbors_directed(recipe_idx, Direction::Outgoing))
+        .collect();
+    let produced: HashSet<_> = recipes.iter()
+        .flat_map(|&recipe_idx| graph.neighbors_directed(recipe_idx, Direction::Incoming))
+        .collect();
+    let num_deps = consumed.difference(&produced).count();
+    let num_outputs = produced.iter()
+        .filter(|&&item_idx| graph.neighbors_directed(item_idx, Direction::Incoming)
+            .any(|consumer_idx| !subgraph.contains(&consumer_idx)))
+        .count();
+    ClusterScore { num_deps, num_outputs }
+}
+
+/// Returns whether the node is an item node of a bipartite graph, i.e., its `kind` is `item`,
+/// falling back to the global `node` attributes of the graph.
+fn is_item(graph: &DotGraph, node_idx: NodeIndex) -> bool {
+    let kind = graph[node_idx].attributes.get("kind").or_else(|| graph.node_attributes.get("kind"));
+    kind.map(AttrValue::as_str) == Some("item")
+}
+
+/// Returns the neighbors of the node in the given direction together with the edge leading to
+/// each of them.
+///
+/// With `dir_hints` on an undirected graph, the direction of each edge is taken from its `dir`
+/// attribute: `back` reverses the edge, `both` counts it in both directions and any other value
+/// keeps the order in which the endpoints were written.
+fn hinted_edges(graph: &DotGraph, node_idx: NodeIndex, direction: Direction, dir_hints: bool) -> Vec<(NodeIndex, EdgeIndex)> {
+    let other = |edge: EdgeReference<Edge>| if edge.source() == node_idx { edge.target() } else { edge.source() };
+    if !dir_hints || graph._type == GraphType::Digraph {
+        return graph.edges_directed(node_idx, direction).map(|edge| (other(edge), edge.id())).collect();
+    }
+    let mut neighbors = Vec::new();
+    for &written in &[Direction::Outgoing, Direction::Incoming] {
+        for edge in graph.edges_directed(node_idx, written) {
+            let matches = match edge.weight().attributes.get("dir").map(AttrValue::as_str) {
+                Some("back") => written != direction,
+                Some("both") => true,
+                _ => written == direction,
+            };
+            if matches {
+                neighbors.push((other(edge), edge.id()));
+            }
+        }
+    }
+    neighbors
+}
+
+/// Returns the nodes the subgraph may grow into, i.e., its neighbors, or in a bipartite graph the
+/// recipes sharing an item with it.
+fn subgraph_neighbors_with_duplicates<'a>(subgraph: &'a HashSet<NodeIndex>, graph: &'a DotGraph, model: GraphModel) -> impl Iterator<Item = NodeIndex> + 'a {
+    subgraph.iter()
+        .copied()
+        .flat_map(move |node_idx| graph.neighbors_undirected(node_idx))
+        .flat_map(move |neighbor_idx| match model {
+            GraphModel::Bipartite if is_item(graph, neighbor_idx) => graph.neighbors_undirected(neighbor_idx).collect(),
+            _ => vec![neighbor_idx],
+        })
+        .filter(move |neighbor_idx| !subgraph.contains(neighbor_idx))
+}
+
+/// Returns all dependencies of the subgraph, which aren't part of the subgraph itself.
+pub fn external_inputs(subgraph: &HashSet<NodeIndex>, graph: &DotGraph) -> HashSet<NodeIndex> {
+    subgraph.iter()
+        .copied()
+        .flat_map(|node_idx| graph.neighbors_directed(node_idx, Direction::Outgoing))
+        .filter(|neighbor_idx| !subgraph.contains(neighbor_idx))
+        .collect()
+}
+
+/// Returns the minimal set of items which must be brought into the cluster to produce all of its
+/// items, i.e., its distinct dependencies which aren't produced inside of the cluster.
+///
+/// In a bipartite graph, items produced by a recipe of the cluster aren't part of the
+/// feedstock, even though they aren't members of the cluster, see
+/// [`GraphModel::Bipartite`](enum.GraphModel.html#variant.Bipartite).
+pub fn feedstock(cluster: &HashSet<NodeIndex>, graph: &DotGraph) -> HashSet<NodeIndex> {
+    external_inputs(cluster, graph).into_iter()
+        .filter(|&node_idx| !is_item(graph, node_idx)
+            || !graph.neighbors_directed(node_idx, Direction::Outgoing).any(|producer_idx| cluster.contains(&producer_idx)))
+        .collect()
+}
+
+/// Returns the score the subgraph would have after adding each of its neighbors, sorted by node
+/// index.
+///
+/// These are the candidates the greedy search considers when growing a cluster.
+pub fn score_frontier(cluster: &HashSet<NodeIndex>, graph: &DotGraph, params: &ClusterParams) -> Vec<(NodeIndex, ClusterScore)> {
+    let neighbors: BTreeSet<_> = subgraph_neighbors_with_duplicates(cluster, graph, params.model).collect();
+    neighbors.into_iter()
+        .map(|node_idx| {
+            let mut candidate = cluster.clone();
+            candidate.insert(node_idx);
+            (node_idx, score_with_params(&candidate, graph, params))
+        }).collect()
+}
+
+/// Memoizes the [`score_frontier`](fn.score_frontier.html) of the last cluster, such that
+/// repeatedly asking for the frontier of an unchanged cluster is cheap.
+///
+/// The cache is invalidated when it's asked for a different cluster. It must only be used with
+/// a single graph and the same parameters.
+#[derive(Debug, Default)]
+pub struct FrontierCache {
+    cluster: HashSet<NodeIndex>,
+    frontier: Option<Vec<(NodeIndex, ClusterScore)>>,
+    computations: usize,
+}
+
+impl FrontierCache {
+    /// Creates an empty cache.
+    pub fn new() -> FrontierCache {
+        FrontierCache::default()
+    }
+
+    /// Returns the frontier of the cluster, only recomputing it if the cluster changed since the
+    /// last call.
+    pub fn score_frontier(&mut self, cluster: &HashSet<NodeIndex>, graph: &DotGraph, params: &ClusterParams) -> &[(NodeIndex, ClusterScore)] {
+        if self.frontier.is_none() || self.cluster != *cluster {
+            self.cluster = cluster.clone();
+            self.frontier = Some(score_frontier(cluster, graph, params));
+            self.computations += 1;
+        }
+        self.frontier.as_ref().unwrap()
+    }
+
+    /// Returns how often the frontier was computed instead of served from the cache.
+    pub fn computations(&self) -> usize {
+        self.computations
+    }
+}
+
+/// Returns all outputs of the subgraph as counted by [`score`](fn.score.html), i.e., items
+/// consumed outside of the subgraph with at least one dependency produced inside of it.
+pub fn outputs(subgraph: &HashSet<NodeIndex>, graph: &DotGraph) -> HashSet<NodeIndex> {
+    subgraph.iter()
+        .copied()
+        .filter(|&node_idx| graph.neighbors_directed(node_idx, Direction::Incoming).any(|neighbor_idx| !subgraph.contains(&neighbor_idx)))
+        .filter(|&node_idx| graph.neighbors_directed(node_idx, Direction::Outgoing).any(|neighbor_idx| subgraph.contains(&neighbor_idx)))
+        .collect()
+}
+
+/// Ranks the external inputs of the cluster by their import burden, highest first.
+///
+/// The burden of an input is its cost as returned by `cost_fn` times the total
+/// [`amount`](../graphviz/struct.Edge.html#method.amount) of it consumed by the cluster.
+/// Use [`import_cost_attribute`](fn.import_cost_attribute.html) to take the cost from the graph.
+pub fn rank_external_inputs(cluster: &HashSet<NodeIndex>, graph: &DotGraph, cost_fn: impl Fn(NodeIndex) -> f64) -> Vec<(NodeIndex, f64)> {
+    let mut amounts = HashMap::new();
+    for &node_idx in cluster {
+        for edge in graph.edges_directed(node_idx, Direction::Outgoing) {
+            if !cluster.contains(&edge.target()) {
+                *amounts.entry(edge.target()).or_insert(0.0) += edge.weight().amount();
+            }
+        }
+    }
+    let mut ranking: Vec<_> = amounts.into_iter()
+        .map(|(node_idx, amount)| (node_idx, cost_fn(node_idx) * amount))
+        .collect();
+    ranking.sort_by(|(a_idx, a), (b_idx, b)| b.total_cmp(a).then(a_idx.cmp(b_idx)));
+    ranking
+}
+
+/// Returns a cost function for [`rank_external_inputs`](fn.rank_external_inputs.html) reading
+/// the `import_cost` attribute of each node, defaulting to 1.
+pub fn import_cost_attribute(graph: &DotGraph) -> impl Fn(NodeIndex) -> f64 + '_ {
+    move |node_idx| graph[node_idx].attribute_f64("import_cost").unwrap_or(1.0)
+}
+
+/// Suggests the external dependency whose recipe, if pulled into the cluster, lowers the number
+/// of dependencies of the cluster the most, together with the resulting score.
+///
+/// Ties are broken by the total score and then by node index.
+/// Returns `None` if no single dependency lowers the number of dependencies.
+pub fn best_internalization(cluster: &HashSet<NodeIndex>, graph: &DotGraph) -> Option<(NodeIndex, ClusterScore)> {
+    let current = score(cluster, graph);
+    external_inputs(cluster, graph).into_iter()
+        .map(|dependency| {
+            let mut internalized = cluster.clone();
+            internalized.insert(dependency);
+            (dependency, score(&internalized, graph))
+        }).filter(|(_, score)| score.num_deps < current.num_deps)
+        .min_by_key(|&(dependency, score)| (score.num_deps, score.total(), dependency))
+}
+
+/// Returns all raw resources the subgraph ultimately needs, i.e., how many mining feeds it requires.
+///
+/// Raw resources within the subgraph itself are not part of the footprint.
+pub fn raw_resource_footprint(cluster: &HashSet<NodeIndex>, graph: &DotGraph) -> HashSet<NodeIndex> {
+    let mut footprint = HashSet::new();
+    for input in external_inputs(cluster, graph) {
+        footprint.extend(graph.dependencies_closure(input).into_iter()
+            .chain(Some(input))
+            .filter(|&node_idx| graph.is_raw_resource(node_idx) && !cluster.contains(&node_idx)));
+    }
+    footprint
+}
+
+/// Returns the internal edge density of the subgraph, i.e., the number of edges between its nodes
+/// divided by the maximum possible number of such edges.
+///
+/// The maximum is `n*(n-1)` for directed and `n*(n-1)/2` for undirected graphs.
+/// Parallel edges between the same nodes are counted once, so the density is at most 1.
+/// Self-loops are ignored and subgraphs with less than two nodes have a density of 0.
+pub fn density(cluster: &HashSet<NodeIndex>, graph: &DotGraph) -> f64 {
+    let n = cluster.len();
+    if n < 2 {
+        return 0.0;
+    }
+    let internal_edges = graph.raw_edges().iter()
+        .filter(|edge| edge.source() != edge.target())
+        .filter(|edge| cluster.contains(&edge.source()) && cluster.contains(&edge.target()))
+        .map(|edge| match graph._type {
+            GraphType::Digraph => (edge.source(), edge.target()),
+            GraphType::Graph => (edge.source().min(edge.target()), edge.source().max(edge.target())),
+        })
+        .collect::<HashSet<_>>()
+        .len();
+    let max_edges = match graph._type {
+        GraphType::Digraph => n * (n - 1),
+        GraphType::Graph => n * (n - 1) / 2,
+    };
+    internal_edges as f64 / max_edges as f64
+}
+
+/// Returns the share of the subgraph's dependencies which are produced inside of it, i.e., the
+/// number of edges from a node of the subgraph to a dependency within the subgraph divided by the
+/// number of edges to any dependency.
+///
+/// A value near 1.0 means the subgraph produces almost everything it needs itself.
+/// A subgraph without any dependencies is completely self-sufficient.
+pub fn self_sufficiency(cluster: &HashSet<NodeIndex>, graph: &DotGraph) -> f64 {
+    let dependencies: Vec<_> = cluster.iter()
+        .flat_map(|&node_idx| graph.neighbors_directed(node_idx, Direction::Outgoing))
+        .collect();
+    if dependencies.is_empty() {
+        return 1.0;
+    }
+    let internal = dependencies.iter().filter(|node_idx| cluster.contains(node_idx)).count();
+    internal as f64 / dependencies.len() as f64
+}
+
+/// Returns the number of distinct items produced and consumed by the subgraph.
+///
+/// Produced items are the items of the subgraph consumed outside of it, as well as final products
+/// without any consumer. Items only consumed inside of the subgraph are intermediates and aren't
+/// counted. Consumed items are the dependencies of any item of the subgraph, inside or outside
+/// of it.
+/// Unlike the edge-based [`score`](fn.score.html), each item is only counted once, no matter how
+/// many edges lead to it.
+pub fn io_item_counts(cluster: &HashSet<NodeIndex>, graph: &DotGraph) -> (usize, usize) {
+    let produced = cluster.iter()
+        .filter(|&&node_idx| {
+            let mut consumers = graph.neighbors_directed(node_idx, Direction::Incoming).peekable();
+            consumers.peek().is_none() || consumers.any(|consumer| !cluster.contains(&consumer))
+        }).count();
+    let consumed: HashSet<_> = cluster.iter()
+        .flat_map(|&node_idx| graph.neighbors_directed(node_idx, Direction::Outgoing))
+        .collect();
+    (produced, consumed.len())
+}
+
+/// Returns the number of distinct `category` attributes among the nodes of the subgraph, i.e.,
+/// how many different machine types are required to build it.
+///
+/// Nodes without a `category` attribute don't count towards any category.
+pub fn category_count(cluster: &HashSet<NodeIndex>, graph: &DotGraph) -> usize {
+    cluster.iter()
+        .filter_map(|&node_idx| graph[node_idx].attributes.get("category"))
+        .collect::<HashSet<_>>()
+        .len()
+}
+
+/// Returns all items consumed by more than `threshold` distinct items, sorted by node index.
+///
+/// Such ubiquitous intermediates (e.g. iron-plate) form hubs dragging unrelated items into a
+/// single cluster, which is why they are good candidates for `ClusterParams::excludes`.
+pub fn high_fanin_items(graph: &DotGraph, threshold: usize) -> Vec<NodeIndex> {
+    graph.node_indices()
+        .filter(|&node_idx| {
+            let consumers: HashSet<_> = graph.neighbors_directed(node_idx, Direction::Incoming).collect();
+            consumers.len() > threshold
+        }).collect()
+}
+
+/// Predicate deciding whether a node is eligible for something.
+pub type NodePredicate = Box<dyn Fn(&Node) -> bool>;
+
+/// Returns a predicate for `ClusterParams::include` matching all items whose numeric `tier`
+/// attribute lies within the given inclusive bounds.
+///
+/// Items outside of the range aren't removed from the graph, such that they still count as
+/// external dependencies and consumers, e.g. lower-tier intermediates become raw inputs.
+/// Items without a numeric `tier` never match.
+pub fn tier_range(min: Option<f64>, max: Option<f64>) -> NodePredicate {
+    Box::new(move |node: &Node| match node.attribute_f64("tier") {
+        Some(tier) => min.is_none_or(|min| tier >= min) && max.is_none_or(|max| tier <= max),
+        None => false,
+    })
+}
+
+/// Parameters of the greedy search.
+///
+/// Can be deserialized, e.g. from a configuration file, in which case missing fields take their
+/// default value.
+/// The `include` predicate can't be deserialized and must be set in code.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClusterParams {
+    /// Ids of the items to grow the first clusters from, in order.
+    ///
+    /// Ids which aren't part of the graph are skipped with a warning.
+    /// After all seeds are used up, clusters are grown from the remaining unclustered items in
+    /// the order of their node index, which makes the search deterministic.
+    pub seeds: Vec<String>,
+    /// Maximum number of items per cluster, unbounded if `None`.
+    pub max_cluster_size: Option<usize>,
+    /// Ids of the items which must not be part of any cluster.
+    ///
+    /// Excluded items still count as dependencies and consumers of the clusters.
+    /// Ids which aren't part of the graph are skipped with a warning.
+    pub excludes: Vec<String>,
+    /// If set, only nodes matching this predicate may be part of a cluster.
+    ///
+    /// Like excluded items, non-matching nodes still count as dependencies and consumers.
+    #[serde(skip)]
+    pub include: Option<NodePredicate>,
+    /// If set, only these nodes may be part of a cluster, e.g. the dependency closure of a single
+    /// product to only cluster the production chain of that product.
+    ///
+    /// Like excluded items, nodes outside of the universe still count as dependencies and
+    /// consumers.
+    #[serde(skip)]
+    pub universe: Option<HashSet<NodeIndex>>,
+    /// Objective the resulting clustering optimizes.
+    pub objective: Objective,
+    /// Keep each cluster connected when refining a clustering.
+    ///
+    /// Moves of the minimax refinement which would disconnect a cluster are rejected, and parts
+    /// of a warm-started cluster which became disconnected from its seed are split off.
+    pub require_connected: bool,
+    /// Order in which clusters are grown from the remaining items once all `seeds` are used up.
+    pub seed_strategy: SeedStrategy,
+    /// Take the direction of edges of undirected graphs from their `dir` attribute when scoring.
+    ///
+    /// This recovers the dependency direction of graphs exported with undirected `--` edges.
+    /// Edges of directed graphs always keep their direction.
+    pub dir_hints: bool,
+    /// Ignore the direction of all edges when scoring, i.e., treat every neighbor of an item as
+    /// both dependency and consumer.
+    pub undirected: bool,
+    /// Keep the members of each `subgraph cluster*` block of the input in the same cluster.
+    ///
+    /// This encodes known subfactories directly in the dot file.
+    /// Nested and overlapping cluster subgraphs are pinned together as a whole, and pinned
+    /// groups may exceed `max_cluster_size`.
+    pub pin_subgraphs: bool,
+    /// Weight of each dependency of a cluster in its score, 1 by default.
+    pub dep_weight: f64,
+    /// Weight of each output of a cluster in its score, 1 by default.
+    ///
+    /// Lower it relative to `dep_weight` if output belts are cheaper than importing inputs.
+    pub output_weight: f64,
+    /// How the nodes of the graph model the production chain.
+    pub model: GraphModel,
+    /// Rhai expression computing the cost of a candidate cluster, replacing the weighted score.
+    ///
+    /// The expression can use the variables `num_deps`, `num_outputs`, `size` and `categories`,
+    /// the latter being the [`category_count`](fn.category_count.html) of the cluster, e.g.
+    /// `num_deps * 2 + num_outputs`.
+    #[cfg(feature = "scripting")]
+    pub score_script: Option<ScoreScript>,
+}
+
+impl Default for ClusterParams {
+    fn default() -> ClusterParams {
+        ClusterParams {
+            seeds: Vec::new(),
+            max_cluster_size: None,
+            excludes: Vec::new(),
+            include: None,
+            universe: None,
+            objective: Objective::default(),
+            require_connected: false,
+            seed_strategy: SeedStrategy::default(),
+            dir_hints: false,
+            undirected: false,
+            pin_subgraphs: false,
+            dep_weight: 1.0,
+            output_weight: 1.0,
+            model: GraphModel::default(),
+            #[cfg(feature = "scripting")]
+            score_script: None,
+        }
+    }
+}
+
+#[cfg(feature = "scripting")]
+thread_local!(static ENGINE: rhai::Engine = rhai::Engine::new());
+
+/// Compiled Rhai expression of [`ClusterParams::score_script`](struct.ClusterParams.html#structfield.score_script).
+///
+/// Deserializing compiles the expression, such that invalid scripts are reported when loading
+/// the parameters instead of during the search.
+#[cfg(feature = "scripting")]
+#[derive(Debug, Clone)]
+pub struct ScoreScript {
+    source: String,
+    ast: rhai::AST,
+}
+
+#[cfg(feature = "scripting")]
+impl ScoreScript {
+    /// Compiles the given expression, returning an error if it isn't a valid expression or
+    /// doesn't evaluate to a number.
+    pub fn compile(source: &str) -> Result<ScoreScript, ScoreScriptError> {
+        let ast = ENGINE.with(|engine| engine.compile_expression(source))
+            .map_err(|e| ScoreScriptError { script: source.to_string(), message: e.to_string() })?;
+        let script = ScoreScript { source: source.to_string(), ast };
+        // catches unknown variables and results which aren't numbers
+        let score = ClusterScore { num_deps: 1, num_outputs: 1 };
+        script.eval(score, 1, 1)?;
+        Ok(script)
+    }
+
+    /// Returns the source of the expression.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Evaluates the expression for a cluster with the given score, number of nodes and number
+    /// of categories.
+    fn eval(&self, score: ClusterScore, size: usize, categories: usize) -> Result<f64, ScoreScriptError> {
+        let error = |message: String| ScoreScriptError { script: self.source.clone(), message };
+        let mut scope = rhai::Scope::new();
+        scope.push("num_deps", score.num_deps as rhai::INT);
+        scope.push("num_outputs", score.num_outputs as rhai::INT);
+        scope.push("size", size as rhai::INT);
+        scope.push("categories", categories as rhai::INT);
+        let result: rhai::Dynamic = ENGINE.with(|engine| engine.eval_ast_with_scope(&mut scope, &self.ast))
+            .map_err(|e| error(e.to_string()))?;
+        result.as_float()
+            .or_else(|_| result.as_int().map(|cost| cost as f64))
+            .map_err(|ty| error(format!("returned {} instead of a number", ty)))
+    }
+}
+
+#[cfg(feature = "scripting")]
+impl<'de> Deserialize<'de> for ScoreScript {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<ScoreScript, D::Error> {
+        let source = String::deserialize(deserializer)?;
+        ScoreScript::compile(&source).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Error returned if a score script isn't a valid expression or fails to evaluate to a number.
+#[cfg(feature = "scripting")]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ScoreScriptError {
+    /// Source of the script.
+    pub script: String,
+    /// Description of what went wrong.
+    pub message: String,
+}
+
+#[cfg(feature = "scripting")]
+impl std::fmt::Display for ScoreScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "invalid score script {:?}: {}", self.script, self.message)
+    }
+}
+
+#[cfg(feature = "scripting")]
+impl std::error::Error for ScoreScriptError {}
+
+/// Groups of nodes which must be part of the same cluster.
+#[derive(Default)]
+struct Pins {
+    groups: Vec<HashSet<NodeIndex>>,
+    group_of: HashMap<NodeIndex, usize>,
+}
+
+impl Pins {
+    /// Collects the pinned groups of the graph according to `params.pin_subgraphs`.
+    fn new(graph: &DotGraph, params: &ClusterParams) -> Pins {
+        let mut pins = Pins::default();
+        if params.pin_subgraphs {
+            let mut subgraphs: Vec<_> = graph.subgraphs.iter().collect();
+            while let Some(subgraph) = subgraphs.pop() {
+                subgraphs.extend(&subgraph.subgraphs);
+                if subgraph.id.starts_with("cluster") {
+                    pins.pin(subgraph.nodes.iter().copied().collect());
+                }
+            }
+        }
+        pins
+    }
+
+    /// Pins the nodes together, merging all groups any of them is already part of.
+    fn pin(&mut self, mut group: HashSet<NodeIndex>) {
+        let mut merged: Vec<_> = group.iter().filter_map(|node_idx| self.group_of.get(node_idx)).copied().collect();
+        merged.sort();
+        merged.dedup();
+        for i in merged {
+            group.extend(self.groups[i].drain());
+        }
+        for &node_idx in &group {
+            self.group_of.insert(node_idx, self.groups.len());
+        }
+        self.groups.push(group);
+    }
+
+    fn is_pinned(&self, node_idx: NodeIndex) -> bool {
+        self.group_of.contains_key(&node_idx)
+    }
+
+    /// Returns the node together with all nodes of `node_set` pinned to it.
+    fn group(&self, node_idx: NodeIndex, node_set: &HashSet<NodeIndex>) -> HashSet<NodeIndex> {
+        let mut group: HashSet<_> = match self.group_of.get(&node_idx) {
+            Some(&i) => self.groups[i].iter().filter(|node_idx| node_set.contains(node_idx)).copied().collect(),
+            None => HashSet::new(),
+        };
+        group.insert(node_idx);
+        group
+    }
+}
+
+/// Objective of the clustering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Objective {
+    /// Minimize the total cut over all clusters, which is what the greedy search does.
+    #[default]
+    TotalCut,
+    /// Minimize the cut of the worst cluster by refining the greedy search with
+    /// [`minimax_cut`](fn.minimax_cut.html), possibly at the cost of a higher total cut.
+    MinimaxCut,
+}
+
+/// How the nodes of the dependency graph model the production chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphModel {
+    /// Each node is an item produced by a recipe of its own, depending on its ingredients.
+    #[default]
+    ItemAsRecipe,
+    /// Nodes with `kind=item` are items, all other nodes are recipes. A recipe depends on the
+    /// items it consumes, and an item depends on the recipes producing it.
+    ///
+    /// Clusters only consist of recipes, while items are shared inputs and outputs between
+    /// them. `dir_hints` and `undirected` don't apply to this model.
+    Bipartite,
+}
+
+/// Order in which the greedy search picks the seeds of clusters after the explicit seeds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeedStrategy {
+    /// Lowest node index first, i.e., the order in which items are declared.
+    #[default]
+    NodeIndex,
+    /// Highest `importance` attribute first, falling back to the highest degree and then to the
+    /// lowest node index for ties.
+    ///
+    /// Items without a numeric `importance` attribute have an importance of 0.
+    Importance,
+}
+
+/// A single cluster found by the greedy search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cluster {
+    /// Id of the item this cluster was grown from.
+    pub seed: String,
+    /// All nodes of this cluster, including the seed.
+    pub nodes: HashSet<NodeIndex>,
+    /// Ids of all nodes of this cluster, which stay valid across different versions of the graph.
+    pub ids: BTreeSet<String>,
+    /// Score of this cluster.
+    pub score: ClusterScore,
+}
+
+impl Cluster {
+    /// Creates a new cluster grown from `seed` consisting of `nodes` and calculates its score.
+    pub fn new(seed: NodeIndex, nodes: HashSet<NodeIndex>, graph: &DotGraph) -> Cluster {
+        Cluster::with_params(seed, nodes, graph, &ClusterParams::default())
+    }
+
+    /// Creates a new cluster like [`new`](#method.new), scoring it according to `params`.
+    pub fn with_params(seed: NodeIndex, nodes: HashSet<NodeIndex>, graph: &DotGraph, params: &ClusterParams) -> Cluster {
+        Cluster {
+            seed: graph[seed].id.clone(),
+            ids: nodes.iter().map(|&node_idx| graph[node_idx].id.clone()).collect(),
+            score: score_with_params(&nodes, graph, params),
+            nodes,
+        }
+    }
+}
+
+/// Summary of the quality metrics of a single cluster.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusterReport {
+    /// Id of the item the cluster was grown from.
+    pub seed: String,
+    /// Number of items of the cluster.
+    pub size: usize,
+    /// Score of the cluster.
+    pub score: ClusterScore,
+    /// Number of edges crossing the boundary of the cluster.
+    pub cut: f64,
+    /// Internal edge density, see [`density`](fn.density.html).
+    pub density: f64,
+    /// Share of dependencies produced inside, see [`self_sufficiency`](fn.self_sufficiency.html).
+    pub self_sufficiency: f64,
+    /// Number of distinct machine types, see [`category_count`](fn.category_count.html).
+    pub category_count: usize,
+    /// Number of distinct items produced for consumers outside or as final products, see
+    /// [`io_item_counts`](fn.io_item_counts.html).
+    pub items_produced: usize,
+    /// Number of distinct items consumed, see [`io_item_counts`](fn.io_item_counts.html).
+    pub items_consumed: usize,
+    /// Sorted ids of the external inputs, see [`external_inputs`](fn.external_inputs.html).
+    pub inputs: Vec<String>,
+    /// Sorted ids of the outputs, see [`outputs`](fn.outputs.html).
+    pub outputs: Vec<String>,
+}
+
+impl ClusterReport {
+    /// Calculates all metrics of the cluster.
+    pub fn new(cluster: &Cluster, graph: &DotGraph) -> ClusterReport {
+        let (items_produced, items_consumed) = io_item_counts(&cluster.nodes, graph);
+        ClusterReport {
+            seed: cluster.seed.clone(),
+            size: cluster.nodes.len(),
+            score: cluster.score,
+            cut: cluster_cut(&cluster.nodes, graph),
+            density: density(&cluster.nodes, graph),
+            self_sufficiency: self_sufficiency(&cluster.nodes, graph),
+            category_count: category_count(&cluster.nodes, graph),
+            items_produced,
+            items_consumed,
+            inputs: sorted_ids(external_inputs(&cluster.nodes, graph), graph),
+            outputs: sorted_ids(outputs(&cluster.nodes, graph), graph),
+        }
+    }
+}
+
+/// Returns the ids of the nodes in alphabetical order.
+fn sorted_ids(nodes: HashSet<NodeIndex>, graph: &DotGraph) -> Vec<String> {
+    let mut ids: Vec<_> = nodes.into_iter().map(|node_idx| graph[node_idx].id.clone()).collect();
+    ids.sort();
+    ids
+}
+
+/// Formats the reports as a GitHub-flavored Markdown table with one row per cluster, e.g. for
+/// pasting into issues.
+///
+/// # Example
+/// ```text
+/// | cluster | size | num_deps | num_outputs | inputs | outputs |
+/// |---|---:|---:|---:|---|---|
+/// | gear | 2 | 1 | 1 | iron-ore | gear |
+/// ```
+pub fn report_markdown(reports: &[ClusterReport]) -> String {
+    let cell = |s: &str| s.replace('|', "\\|");
+    let mut markdown = String::from("| cluster | size | num_deps | num_outputs | inputs | outputs |\n|---|---:|---:|---:|---|---|\n");
+    for report in reports {
+        let inputs: Vec<_> = report.inputs.iter().map(|id| cell(id)).collect();
+        let outputs: Vec<_> = report.outputs.iter().map(|id| cell(id)).collect();
+        markdown += &format!("| {} | {} | {} | {} | {} | {} |\n", cell(&report.seed), report.size, report.score.num_deps,
+                             report.score.num_outputs, inputs.join(", "), outputs.join(", "));
+    }
+    markdown
+}
+
+/// Order of the clusters within a [`Clustering`](struct.Clustering.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClusterOrder {
+    /// Largest clusters first.
+    Size,
+    /// Alphabetically by the id of the seed.
+    SeedId,
+    /// Best (lowest) score first.
+    Score,
+}
+
+/// Result of the greedy search, partitioning all items into clusters.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Clustering {
+    /// All found clusters in discovery order, unless reordered with `sort_by`.
+    pub clusters: Vec<Cluster>,
+}
+
+impl Clustering {
+    /// Reorders the clusters deterministically.
+    ///
+    /// Ties are broken by the id of the seed, which is unique within a clustering.
+    pub fn sort_by(&mut self, order: ClusterOrder) {
+        match order {
+            ClusterOrder::Size => self.clusters.sort_by(|a, b| b.nodes.len().cmp(&a.nodes.len())
+                .then_with(|| a.seed.cmp(&b.seed))),
+            ClusterOrder::SeedId => self.clusters.sort_by(|a, b| a.seed.cmp(&b.seed)),
+            ClusterOrder::Score => self.clusters.sort_by(|a, b| a.score.total().cmp(&b.score.total())
+                .then_with(|| a.seed.cmp(&b.seed))),
+        }
+    }
+
+    /// Returns the report of each cluster in the order of the clustering.
+    pub fn reports(&self, graph: &DotGraph) -> Vec<ClusterReport> {
+        self.clusters.iter().map(|cluster| ClusterReport::new(cluster, graph)).collect()
+    }
+
+    /// Returns a map from each clustered node to the index of its cluster.
+    pub fn assignment(&self) -> HashMap<NodeIndex, usize> {
+        self.clusters.iter()
+            .enumerate()
+            .flat_map(|(i, cluster)| cluster.nodes.iter().map(move |&node_idx| (node_idx, i)))
+            .collect()
+    }
+}
+
+/// Returns how stable the clustering stayed between two versions of the graph, from 0 (every
+/// pair of items changed) to 1 (unchanged).
+///
+/// This is the fraction of pairs of items, which are either in the same cluster in both
+/// clusterings or in different clusters in both clusterings, i.e., the Rand index.
+/// Items are matched by id, and only items part of both clusterings are considered.
+pub fn stability(old: &Clustering, new: &Clustering) -> f64 {
+    let assignment = |clustering: &Clustering| -> HashMap<String, usize> {
+        clustering.clusters.iter()
+            .enumerate()
+            .flat_map(|(i, cluster)| cluster.ids.iter().map(move |id| (id.clone(), i)))
+            .collect()
+    };
+    let (old, new) = (assignment(old), assignment(new));
+    let mut ids: Vec<_> = old.keys().filter(|id| new.contains_key(*id)).collect();
+    ids.sort();
+
+    let mut pairs = 0;
+    let mut stable = 0;
+    for (i, a) in ids.iter().enumerate() {
+        for b in &ids[i + 1..] {
+            pairs += 1;
+            if (old[*a] == old[*b]) == (new[*a] == new[*b]) {
+                stable += 1;
+            }
+        }
+    }
+    if pairs == 0 {
+        return 1.0;
+    }
+    stable as f64 / pairs as f64
+}
+
+/// Groups near-identical clusterings, e.g. of runs with different parameters, and returns the
+/// index of one representative of each group in ascending order.
+///
+/// Each clustering is compared with the representatives found so far, and is part of the group
+/// of the first one whose [`stability`](fn.stability.html) (Rand index) with it exceeds
+/// `threshold`. Otherwise, it becomes a representative of its own.
+pub fn dedup_clusterings(runs: &[Clustering], threshold: f64) -> Vec<usize> {
+    let mut representatives: Vec<usize> = Vec::new();
+    for (i, run) in runs.iter().enumerate() {
+        if !representatives.iter().any(|&representative| stability(&runs[representative], run) > threshold) {
+            representatives.push(i);
+        }
+    }
+    representatives
+}
+
+/// Aggregated metrics of a whole clustering.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusteringMetrics {
+    /// Number of clusters.
+    pub num_clusters: usize,
+    /// Total cut, see [`cut_cost`](fn.cut_cost.html).
+    pub cut_cost: f64,
+    /// Highest cut of any single cluster, see [`peak_cut`](fn.peak_cut.html).
+    pub peak_cut: f64,
+    /// Sum of the scores of all clusters.
+    pub total_score: usize,
+}
+
+impl ClusteringMetrics {
+    /// Calculates the metrics of the clustering.
+    pub fn new(clustering: &Clustering, graph: &DotGraph) -> ClusteringMetrics {
+        ClusteringMetrics {
+            num_clusters: clustering.clusters.len(),
+            cut_cost: cut_cost(clustering, graph),
+            peak_cut: peak_cut(clustering, graph),
+            total_score: clustering.clusters.iter().map(|cluster| cluster.score.total()).sum(),
+        }
+    }
+}
+
+/// Side by side comparison of two clusterings of the same graph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusteringComparison {
+    /// Metrics of the first clustering.
+    pub left: ClusteringMetrics,
+    /// Metrics of the second clustering.
+    pub right: ClusteringMetrics,
+    /// How similar the partitions are, see [`stability`](fn.stability.html).
+    pub stability: f64,
+}
+
+/// Compares the metrics of both clusterings, e.g. the clusterings found with directed and
+/// undirected scoring.
+///
+/// The scores are the ones stored in the clusters, i.e., each clustering keeps the score of the
+/// parameters it was found with.
+pub fn compare(left: &Clustering, right: &Clustering, graph: &DotGraph) -> ClusteringComparison {
+    ClusteringComparison {
+        left: ClusteringMetrics::new(left, graph),
+        right: ClusteringMetrics::new(right, graph),
+        stability: stability(left, right),
+    }
+}
+
+/// Returns the index of the cluster each direct dependency of `item` is part of, or `None` if
+/// the dependency isn't part of any cluster (e.g. because it was excluded).
+pub fn dependency_cluster_map(item: NodeIndex, clustering: &Clustering, graph: &DotGraph) -> HashMap<NodeIndex, Option<usize>> {
+    let assignment = clustering.assignment();
+    graph.neighbors_directed(item, Direction::Outgoing)
+        .map(|dependency| (dependency, assignment.get(&dependency).copied()))
+        .collect()
+}
+
+/// Returns the flow between each pair of clusters, keyed by `(producing cluster, consuming cluster)`.
+///
+/// The flow of an edge is its [`amount`](../graphviz/struct.Edge.html#method.amount), which is in
+/// items per second if the amounts are annotated as rates.
+/// Edges to nodes which aren't part of any cluster are ignored.
+pub fn inter_cluster_flows(clustering: &Clustering, graph: &DotGraph) -> HashMap<(usize, usize), f64> {
+    let mut flows = HashMap::new();
+    for boundary in boundary_edges(clustering, graph) {
+        // the target of an edge is the dependency, i.e., produces the items consumed by the source
+        if let (Some(from), Some(to)) = (boundary.to_cluster, boundary.from_cluster) {
+            *flows.entry((from, to)).or_insert(0.0) += graph[boundary.edge].amount();
+        }
+    }
+    flows
+}
+
+/// Condenses each cluster into a single node, e.g. for a high-level map of the factory.
+///
+/// The node of the `N`th cluster has the id `cluster_N` and is labeled with the seed and the
+/// number of items of the cluster.
+/// Like in the original graph, an edge points from the consuming to the producing cluster.
+/// Its `amount` is the sum of the [`inter_cluster_flows`](fn.inter_cluster_flows.html) between
+/// both clusters.
+pub fn condense(clustering: &Clustering, graph: &DotGraph) -> DotGraph {
+    let nodes = clustering.clusters.iter()
+        .enumerate()
+        .map(|(i, cluster)| {
+            let mut attributes = HashMap::new();
+            attributes.insert("label".to_string(), format!("{} ({} items)", cluster.seed, cluster.nodes.len()).into());
+            Node::new(format!("cluster_{}", i), attributes)
+        }).collect();
+    let mut flows: Vec<_> = inter_cluster_flows(clustering, graph).into_iter().collect();
+    flows.sort_by_key(|&(pair, _)| pair);
+    let edges = flows.into_iter()
+        .map(|((from, to), flow)| {
+            let mut attributes = HashMap::new();
+            attributes.insert("amount".to_string(), flow.to_string().into());
+            (Edge::new(attributes), NodeIndex::new(to), NodeIndex::new(from))
+        }).collect();
+    DotGraphBuilder::new(graph._type)
+        .nodes(nodes)
+        .edges(edges)
+        .build()
+}
+
+/// Number of recipes listed in the labels of [`condense_with_html_labels`](fn.condense_with_html_labels.html).
+const HTML_LABEL_RECIPES: usize = 5;
+
+/// Escapes the characters with a special meaning in graphviz' HTML strings.
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Condenses each cluster into a single node like [`condense`](fn.condense.html), labeled with
+/// an HTML table summarizing the cluster, which graphviz renders as a table.
+///
+/// The table lists the seed, the number of inputs and outputs according to the score of the
+/// cluster, and its top five recipes, which are the items with the most edges. Ties are broken
+/// by id.
+pub fn condense_with_html_labels(clustering: &Clustering, graph: &DotGraph) -> DotGraph {
+    let mut condensed = condense(clustering, graph);
+    for (i, cluster) in clustering.clusters.iter().enumerate() {
+        let mut recipes: Vec<_> = cluster.nodes.iter()
+            .map(|&node_idx| (graph.neighbors_undirected(node_idx).count(), &graph[node_idx].id))
+            .collect();
+        recipes.sort_by(|(a_degree, a_id), (b_degree, b_id)| b_degree.cmp(a_degree).then(a_id.cmp(b_id)));
+        let mut rows = vec![
+            format!("<tr><td><b>{}</b></td></tr>", escape_html(&cluster.seed)),
+            format!("<tr><td>inputs: {}, outputs: {}</td></tr>", cluster.score.num_deps, cluster.score.num_outputs),
+        ];
+        rows.extend(recipes.iter().take(HTML_LABEL_RECIPES).map(|(_, id)| format!("<tr><td>{}</td></tr>", escape_html(id))));
+        if recipes.len() > HTML_LABEL_RECIPES {
+            rows.push(format!("<tr><td>… {} more</td></tr>", recipes.len() - HTML_LABEL_RECIPES));
+        }
+        let label = format!("<<table border=\"0\" cellborder=\"1\">{}</table>>", rows.concat());
+        let node = &mut condensed[NodeIndex::new(i)];
+        node.attributes.insert("label".to_string(), AttrValue::Html(label));
+        node.attributes.insert("shape".to_string(), "plain".into());
+    }
+    condensed
+}
+
+/// Returns the number of belts required between each pair of clusters to transport their
+/// [`inter_cluster_flows`](fn.inter_cluster_flows.html) on belts moving `belt_capacity` items per
+/// second, or `None` if there is any flow but `belt_capacity` isn't positive.
+pub fn inter_cluster_belts(clustering: &Clustering, graph: &DotGraph, belt_capacity: f64) -> Option<HashMap<(usize, usize), usize>> {
+    inter_cluster_flows(clustering, graph).into_iter()
+        .map(|(pair, flow)| Some((pair, factorio::belts_needed(flow, belt_capacity)?)))
+        .collect()
+}
+
+/// Returns all raw resources feeding more than one cluster, each mapped to the indices of the
+/// clusters (transitively) depending on it in ascending order.
+///
+/// These are candidates for shared mining outposts.
+/// The clusters of a raw resource are the ones containing it in their
+/// [`raw_resource_footprint`](fn.raw_resource_footprint.html), i.e., a cluster containing the
+/// raw resource itself isn't counted.
+pub fn shared_raw_resources(clustering: &Clustering, graph: &DotGraph) -> HashMap<NodeIndex, Vec<usize>> {
+    let mut consumers: HashMap<_, Vec<_>> = HashMap::new();
+    for (i, cluster) in clustering.clusters.iter().enumerate() {
+        for resource in raw_resource_footprint(&cluster.nodes, graph) {
+            consumers.entry(resource).or_default().push(i);
+        }
+    }
+    consumers.retain(|_, clusters| clusters.len() > 1);
+    consumers
+}
+
+/// Returns the scores of `from` and `to` if `node` was moved from `from` to `to`, without
+/// modifying either cluster.
+///
+/// Together with the current scores `from.score` and `to.score`, this evaluates a hypothetical
+/// move, e.g. when a user drags an item to another cluster.
+///
+/// # Panics
+///
+/// Panics if `node` isn't part of `from`.
+pub fn move_node_delta(node: NodeIndex, from: &Cluster, to: &Cluster, graph: &DotGraph) -> (ClusterScore, ClusterScore) {
+    assert!(from.nodes.contains(&node), "{} isn't part of the cluster of {}", graph[node].id, from.seed);
+    let mut from_nodes = from.nodes.clone();
+    from_nodes.remove(&node);
+    let mut to_nodes = to.nodes.clone();
+    to_nodes.insert(node);
+    (score(&from_nodes, graph), score(&to_nodes, graph))
+}
+
+/// Returns the total cost of all edges between different clusters, where each edge costs 1.
+///
+/// Edges to nodes which aren't part of any cluster count as crossing as well.
+pub fn cut_cost(clustering: &Clustering, graph: &DotGraph) -> f64 {
+    edges_crossing(clustering, graph).len() as f64
+}
+
+/// Returns all edges between different clusters, including edges to nodes which aren't part of
+/// any cluster.
+pub fn edges_crossing(clustering: &Clustering, graph: &DotGraph) -> Vec<EdgeIndex> {
+    boundary_edges(clustering, graph).map(|boundary| boundary.edge).collect()
+}
+
+/// An edge whose endpoints aren't part of the same cluster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BoundaryEdge {
+    /// Index of the edge.
+    pub edge: EdgeIndex,
+    /// Index of the cluster of the edge's source, i.e., the consuming item, if it's clustered.
+    pub from_cluster: Option<usize>,
+    /// Index of the cluster of the edge's target, i.e., the dependency, if it's clustered.
+    pub to_cluster: Option<usize>,
+    /// Source of the edge.
+    pub from_node: NodeIndex,
+    /// Target of the edge.
+    pub to_node: NodeIndex,
+}
+
+/// Iterates over all edges crossing a cluster boundary once, in the order of their edge index.
+///
+/// This includes edges to nodes which aren't part of any cluster, like excluded items.
+/// Edges between two unclustered nodes don't belong to a common cluster either and are thus
+/// part of the boundary as well.
+pub fn boundary_edges<'a>(clustering: &Clustering, graph: &'a DotGraph) -> impl Iterator<Item = BoundaryEdge> + 'a {
+    let assignment = clustering.assignment();
+    graph.edge_references()
+        .map(move |edge| BoundaryEdge {
+            edge: edge.id(),
+            from_cluster: assignment.get(&edge.source()).copied(),
+            to_cluster: assignment.get(&edge.target()).copied(),
+            from_node: edge.source(),
+            to_node: edge.target(),
+        }).filter(|boundary| boundary.from_cluster.is_none() || boundary.from_cluster != boundary.to_cluster)
+}
+
+/// Returns all nodes of the subgraph reachable from `start` without leaving the subgraph,
+/// ignoring the direction of edges.
+fn connected_component(start: NodeIndex, subgraph: &HashSet<NodeIndex>, graph: &DotGraph) -> HashSet<NodeIndex> {
+    let mut component = HashSet::new();
+    component.insert(start);
+    let mut stack = vec![start];
+    while let Some(node_idx) = stack.pop() {
+        for neighbor_idx in graph.neighbors_undirected(node_idx) {
+            if subgraph.contains(&neighbor_idx) && component.insert(neighbor_idx) {
+                stack.push(neighbor_idx);
+            }
+        }
+    }
+    component
+}
+
+/// Returns a small connected subgraph containing all given items, i.e., an approximation of the
+/// Steiner tree of the items, ignoring the direction of edges.
+///
+/// Starting from the first item, the item closest to the subgraph is connected along one of its
+/// shortest paths, until all items are connected. Paths through lower node indices are preferred
+/// on ties.
+/// Items without any path to the others stay unconnected, but are still part of the result.
+pub fn minimal_enclosing(items: &[NodeIndex], graph: &DotGraph) -> HashSet<NodeIndex> {
+    let mut enclosing: HashSet<_> = items.iter().take(1).copied().collect();
+    let mut remaining: HashSet<_> = items.iter().copied().filter(|item| !enclosing.contains(item)).collect();
+    while !remaining.is_empty() {
+        // breadth-first search from the whole subgraph, remembering where each node was reached from
+        let mut start: Vec<_> = enclosing.iter().copied().collect();
+        start.sort();
+        let mut parents: HashMap<NodeIndex, Option<NodeIndex>> = start.iter().map(|&node_idx| (node_idx, None)).collect();
+        let mut queue: VecDeque<_> = start.into_iter().collect();
+        let mut found = None;
+        while let Some(node_idx) = queue.pop_front() {
+            if remaining.contains(&node_idx) {
+                found = Some(node_idx);
+                break;
+            }
+            let mut neighbors: Vec<_> = graph.neighbors_undirected(node_idx).collect();
+            neighbors.sort();
+            for neighbor_idx in neighbors {
+                if let Entry::Vacant(entry) = parents.entry(neighbor_idx) {
+                    entry.insert(Some(node_idx));
+                    queue.push_back(neighbor_idx);
+                }
+            }
+        }
+        match found {
+            Some(mut node_idx) => {
+                remaining.remove(&node_idx);
+                while enclosing.insert(node_idx) {
+                    node_idx = parents[&node_idx].unwrap();
+                }
+            }
+            None => {
+                // the remaining items are disconnected from the subgraph
+                enclosing.extend(remaining.drain());
+            }
+        }
+    }
+    enclosing
+}
+
+/// Returns the items of the cluster in an order to build them, where each item comes after all
+/// of its dependencies within the cluster, e.g. as checklist for placing its recipes.
+///
+/// Dependencies outside of the cluster are ignored. Among items whose dependencies are all
+/// built, the one with the lowest node index comes first.
+/// Returns an error naming an item of a cycle if the items of the cluster depend on each other
+/// cyclically.
+pub fn build_order(cluster: &HashSet<NodeIndex>, graph: &DotGraph) -> Result<Vec<NodeIndex>, NotADag> {
+    let internal = |node_idx: NodeIndex, direction| graph.neighbors_directed(node_idx, direction)
+        .filter(|neighbor_idx| cluster.contains(neighbor_idx))
+        .collect::<HashSet<_>>();
+    let mut missing: HashMap<_, _> = cluster.iter().map(|&node_idx| (node_idx, internal(node_idx, Direction::Outgoing).len())).collect();
+    let mut ready: BinaryHeap<_> = missing.iter().filter(|&(_, &count)| count == 0).map(|(&node_idx, _)| Reverse(node_idx)).collect();
+    let mut order = Vec::new();
+    while let Some(Reverse(node_idx)) = ready.pop() {
+        missing.remove(&node_idx);
+        order.push(node_idx);
+        for consumer_idx in internal(node_idx, Direction::Incoming) {
+            let count = missing.get_mut(&consumer_idx).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                ready.push(Reverse(consumer_idx));
+            }
+        }
+    }
+    if missing.is_empty() {
+        return Ok(order);
+    }
+    // all remaining items wait for another remaining item, so following them leads into a cycle
+    let mut node_idx = *missing.keys().min().unwrap();
+    let mut visited = HashSet::new();
+    while visited.insert(node_idx) {
+        node_idx = internal(node_idx, Direction::Outgoing).into_iter()
+            .filter(|dependency_idx| missing.contains_key(dependency_idx))
+            .min()
+            .unwrap();
+    }
+    Err(NotADag { id: graph[node_idx].id.clone() })
+}
+
+/// Returns whether the subgraph is connected, ignoring the direction of edges.
+pub fn is_connected(subgraph: &HashSet<NodeIndex>, graph: &DotGraph) -> bool {
+    match subgraph.iter().next() {
+        Some(&start) => connected_component(start, subgraph, graph).len() == subgraph.len(),
+        None => true,
+    }
+}
+
+/// Panics if any cluster of the clustering isn't connected.
+pub fn assert_connected(clustering: &Clustering, graph: &DotGraph) {
+    for cluster in &clustering.clusters {
+        assert!(is_connected(&cluster.nodes, graph), "cluster grown from {} is not connected: {:?}", cluster.seed, cluster.ids);
+    }
+}
+
+/// Returns the cut of a single cluster, i.e., the number of edges with exactly one endpoint in it.
+pub fn cluster_cut(cluster: &HashSet<NodeIndex>, graph: &DotGraph) -> f64 {
+    graph.raw_edges().iter()
+        .filter(|edge| cluster.contains(&edge.source()) != cluster.contains(&edge.target()))
+        .count() as f64
+}
+
+/// Returns the highest cut of any single cluster of the clustering.
+pub fn peak_cut(clustering: &Clustering, graph: &DotGraph) -> f64 {
+    clustering.clusters.iter()
+        .map(|cluster| cluster_cut(&cluster.nodes, graph))
+        .fold(0.0, f64::max)
+}
+
+/// Refines the clustering with a local search reducing the highest cut of any single cluster.
+///
+/// Nodes are moved along edges into the cluster of their neighbor as long as this lowers the
+/// peak cut, even if the total cut rises.
+/// Seeds and pinned nodes are never moved, which keeps the number of clusters the same, and no
+/// cluster grows beyond `params.max_cluster_size`.
+pub fn minimax_cut(clustering: &Clustering, graph: &DotGraph, params: &ClusterParams) -> Clustering {
+    minimax_cut_with_max_size(clustering, graph, params, params.max_cluster_size)
+}
+
+fn minimax_cut_with_max_size(clustering: &Clustering, graph: &DotGraph, params: &ClusterParams, max_cluster_size: Option<usize>) -> Clustering {
+    let seeds: Vec<_> = clustering.clusters.iter().map(|cluster| graph.id_map()[&cluster.seed]).collect();
+    let pins = Pins::new(graph, params);
+    let mut clusters: Vec<_> = clustering.clusters.iter().map(|cluster| cluster.nodes.clone()).collect();
+    let mut cuts: Vec<_> = clusters.iter().map(|cluster| cluster_cut(cluster, graph)).collect();
+
+    loop {
+        let assignment: HashMap<_, _> = clusters.iter()
+            .enumerate()
+            .flat_map(|(i, cluster)| cluster.iter().map(move |&node_idx| (node_idx, i)))
+            .collect();
+        let peak = cuts.iter().cloned().fold(0.0, f64::max);
+
+        // (new peak, node, from, to, new cut of from, new cut of to)
+        let mut best: Option<(f64, NodeIndex, usize, usize, f64, f64)> = None;
+        for edge in graph.raw_edges() {
+            for &(node_idx, neighbor_idx) in &[(edge.source(), edge.target()), (edge.target(), edge.source())] {
+                let (from, to) = match (assignment.get(&node_idx), assignment.get(&neighbor_idx)) {
+                    (Some(&from), Some(&to)) if from != to => (from, to),
+                    _ => continue,
+                };
+                if seeds.contains(&node_idx) || pins.is_pinned(node_idx) || max_cluster_size.is_some_and(|max| clusters[to].len() >= max) {
+                    continue;
+                }
+                let mut from_cluster = clusters[from].clone();
+                from_cluster.remove(&node_idx);
+                if params.require_connected && !is_connected(&from_cluster, graph) {
+                    continue;
+                }
+                let mut to_cluster = clusters[to].clone();
+                to_cluster.insert(node_idx);
+                let (from_cut, to_cut) = (cluster_cut(&from_cluster, graph), cluster_cut(&to_cluster, graph));
+                let new_peak = cuts.iter()
+                    .enumerate()
+                    .filter(|&(i, _)| i != from && i != to)
+                    .map(|(_, &cut)| cut)
+                    .fold(from_cut.max(to_cut), f64::max);
+                if new_peak < peak && best.is_none_or(|best| new_peak < best.0) {
+                    best = Some((new_peak, node_idx, from, to, from_cut, to_cut));
+                }
+            }
+        }
+
+        match best {
+            Some((_, node_idx, from, to, from_cut, to_cut)) => {
+                debug!("moving {} to reduce the peak cut", graph[node_idx].id);
+                clusters[from].remove(&node_idx);
+                clusters[to].insert(node_idx);
+                cuts[from] = from_cut;
+                cuts[to] = to_cut;
+            }
+            None => break,
+        }
+    }
+
+    Clustering {
+        clusters: seeds.into_iter()
+            .zip(clusters)
+            .map(|(seed, nodes)| Cluster::with_params(seed, nodes, graph, params))
+            .collect(),
+    }
+}
+
+/// Merges adjacent clusters cheaply connected to each other, as a cleanup after the greedy search.
+///
+/// As long as any pair of clusters is connected by at least one but fewer than `threshold`
+/// edges, the pair with the fewest edges between them is merged, preferring the pair with the
+/// lowest indices on ties.
+/// The merged cluster keeps the seed and the position of the earlier cluster, and its score is
+/// recomputed according to `params`.
+pub fn merge_cheap_boundaries(clustering: &Clustering, graph: &DotGraph, params: &ClusterParams, threshold: f64) -> Clustering {
+    let mut clustering = clustering.clone();
+    loop {
+        let mut cuts: HashMap<(usize, usize), f64> = HashMap::new();
+        for boundary in boundary_edges(&clustering, graph) {
+            if let (Some(a), Some(b)) = (boundary.from_cluster, boundary.to_cluster) {
+                *cuts.entry((a.min(b), a.max(b))).or_insert(0.0) += 1.0;
+            }
+        }
+        let cheapest = cuts.into_iter()
+            .filter(|&(_, cut)| cut < threshold)
+            .min_by(|(a_pair, a), (b_pair, b)| a.partial_cmp(b).unwrap().then(a_pair.cmp(b_pair)));
+        let (keep, merge) = match cheapest {
+            Some((pair, _)) => pair,
+            None => break,
+        };
+        let merged = clustering.clusters.remove(merge);
+        let kept = &clustering.clusters[keep];
+        debug!("merging the clusters of {} and {}", kept.seed, merged.seed);
+        let nodes = kept.nodes.union(&merged.nodes).copied().collect();
+        clustering.clusters[keep] = Cluster::with_params(graph.id_map()[&kept.seed], nodes, graph, params);
+    }
+    clustering
+}
+
+/// Returns a copy of the graph with the nodes of each cluster filled in a color of their own.
+///
+/// All original attributes are preserved. Colors are taken from graphviz' `set312` color scheme
+/// and repeat after 12 clusters. Unclustered nodes keep their original look, and nodes with a
+/// list of fill colors (a gradient) keep their fill colors.
+pub fn colored_graph(clustering: &Clustering, graph: &DotGraph) -> DotGraph {
+    let mut colored = graph.clone();
+    for (i, cluster) in clustering.clusters.iter().enumerate() {
+        for &node_idx in &cluster.nodes {
+            let node = &mut colored[node_idx];
+            node.add_style("filled");
+            node.attributes.insert("colorscheme".to_string(), "set312".into());
+            graphviz::set_color(&mut node.attributes, "fillcolor", &(i % 12 + 1).to_string());
+        }
+    }
+    colored
+}
+
+/// Writes the graph in dot format with each cluster wrapped in a `subgraph cluster_N` block,
+/// such that graphviz draws a labeled box around it.
+///
+/// `N` is the index of the cluster in the clustering.
+/// Edges crossing cluster boundaries are drawn red and thicker, such that the logistics between
+/// clusters stand out. Edges with a list of colors keep their colors and are only drawn thicker.
+pub fn write_clustered_dot<W: Write>(clustering: &Clustering, graph: &DotGraph, writer: &mut W) -> io::Result<()> {
+    let subgraphs: Vec<_> = clustering.clusters.iter()
+        .enumerate()
+        .map(|(i, cluster)| {
+            let mut attributes = HashMap::new();
+            attributes.insert("label".to_string(), format!("{} ({} items)", cluster.seed, cluster.nodes.len()).into());
+            let mut nodes: Vec<_> = cluster.nodes.iter().copied().collect();
+            nodes.sort();
+            Subgraph::new(format!("cluster_{}", i), attributes, nodes)
+        }).collect();
+    let mut graph = graph.clone();
+    for edge_idx in edges_crossing(clustering, &graph) {
+        let attributes = &mut graph[edge_idx].attributes;
+        graphviz::set_color(attributes, "color", "red");
+        attributes.insert("penwidth".to_string(), "2".into());
+    }
+    graph.write_with_subgraphs(writer, &subgraphs)
+}
+
+/// Runs the greedy search once for each given maximum cluster size, returning the cut cost of
+/// each resulting clustering.
+///
+/// This helps to find the "elbow" of the cut cost, i.e., a good granularity of the clustering.
+/// The `max_cluster_size` of `params` is ignored.
+pub fn sweep(graph: &DotGraph, sizes: &[usize], params: &ClusterParams) -> Vec<(usize, f64)> {
+    sizes.iter()
+        .map(|&size| (size, cut_cost(&cluster_with_max_size(graph, params, Some(size), None), graph)))
+        .collect()
+}
+
+/// Partitions the whole graph into clusters using the greedy search.
+///
+/// Clusters are grown one after another, first from the configured seeds, then from the items
+/// which aren't part of any cluster yet.
+/// A cluster only ever grows into items not part of a previous cluster.
+pub fn greedy_cluster(graph: &DotGraph, params: &ClusterParams) -> Clustering {
+    greedy_cluster_with_callback(graph, params, None)
+}
+
+/// Callback invoked with the nodes and score of each cluster once the greedy search finished
+/// growing it.
+pub type ClusterCallback<'a> = &'a dyn Fn(&HashSet<NodeIndex>, ClusterScore);
+
+/// Partitions the whole graph like [`greedy_cluster`](fn.greedy_cluster.html), calling
+/// `on_cluster_complete` for each cluster as soon as it's finished, e.g. to show partial results
+/// of long runs.
+///
+/// The callback receives the clusters of the greedy search, i.e., before they are refined
+/// according to `params.objective`.
+pub fn greedy_cluster_with_callback(graph: &DotGraph, params: &ClusterParams, on_cluster_complete: Option<ClusterCallback>) -> Clustering {
+    cluster_with_max_size(graph, params, params.max_cluster_size, on_cluster_complete)
+}
+
+fn cluster_with_max_size(graph: &DotGraph, params: &ClusterParams, max_cluster_size: Option<usize>,
+                         on_cluster_complete: Option<ClusterCallback>) -> Clustering {
+    cluster_with_log(graph, params, max_cluster_size, on_cluster_complete, &mut DecisionLog::default())
+}
+
+fn cluster_with_log(graph: &DotGraph, params: &ClusterParams, max_cluster_size: Option<usize>,
+                    on_cluster_complete: Option<ClusterCallback>, log: &mut DecisionLog) -> Clustering {
+    let mut node_set = clusterable_nodes(graph, params);
+    let pins = Pins::new(graph, params);
+    let mut clustering = Clustering::default();
+    cluster_remaining(graph, params, &pins, &mut node_set, &mut clustering, max_cluster_size, on_cluster_complete, log);
+    apply_objective(clustering, graph, params, max_cluster_size)
+}
+
+/// A node joining a cluster during the greedy search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Decision {
+    /// Index of the cluster within the clustering of the greedy search.
+    pub cluster: usize,
+    /// The node joining the cluster.
+    pub node: NodeIndex,
+    /// Node of the cluster the node was reached from, or `None` for the seed.
+    ///
+    /// Nodes pinned to another node are reached from that node.
+    pub parent: Option<NodeIndex>,
+    /// Score of the cluster after the node joined, as seen by the greedy search.
+    pub score: ClusterScore,
+}
+
+/// All decisions of the greedy search in the order they were made.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DecisionLog {
+    /// The decisions, starting with the seed of each cluster.
+    pub decisions: Vec<Decision>,
+}
+
+impl DecisionLog {
+    /// Records that `nodes` joined the cluster, where the first one was reached from `parent`
+    /// and all others are pinned to the first one.
+    fn record(&mut self, cluster: usize, nodes: &HashSet<NodeIndex>, first: NodeIndex, parent: Option<NodeIndex>, score: ClusterScore) {
+        self.decisions.push(Decision { cluster, node: first, parent, score });
+        let mut pinned: Vec<_> = nodes.iter().copied().filter(|&node_idx| node_idx != first).collect();
+        pinned.sort();
+        for node_idx in pinned {
+            self.decisions.push(Decision { cluster, node: node_idx, parent: Some(first), score });
+        }
+    }
+}
+
+/// Renders the decision log as one tree per cluster rooted at its seed, where each node is
+/// indented by four spaces beneath the node it was reached from.
+///
+/// Each line shows the node and the score of the cluster when it joined, e.g.
+/// `    plate (score: ClusterScore { num_deps: 1, num_outputs: 1 })`.
+/// Clusters and the children of each node are in the order of the log.
+pub fn decision_tree_string(log: &DecisionLog, graph: &DotGraph) -> String {
+    fn render(decision: &Decision, depth: usize, log: &DecisionLog, graph: &DotGraph, tree: &mut String) {
+        tree.push_str(&format!("{}{} (score: {:?})\n", "    ".repeat(depth), graph[decision.node].id, decision.score));
+        for child in log.decisions.iter().filter(|child| child.cluster == decision.cluster && child.parent == Some(decision.node)) {
+            render(child, depth + 1, log, graph, tree);
+        }
+    }
+    let mut tree = String::new();
+    for seed in log.decisions.iter().filter(|decision| decision.parent.is_none()) {
+        render(seed, 0, log, graph, &mut tree);
+    }
+    tree
+}
+
+/// Partitions the whole graph like [`greedy_cluster`](fn.greedy_cluster.html), additionally
+/// returning the log of all decisions of the greedy search.
+///
+/// The log describes the greedy search, i.e., the clusters before they are refined according to
+/// `params.objective`.
+pub fn greedy_cluster_with_log(graph: &DotGraph, params: &ClusterParams) -> (Clustering, DecisionLog) {
+    let mut log = DecisionLog::default();
+    let clustering = cluster_with_log(graph, params, params.max_cluster_size, None, &mut log);
+    (clustering, log)
+}
+
+/// Runs the greedy search and writes one dot file per decision to `dir`, showing the clusters
+/// growing step by step, e.g. to stitch them into an animation.
+///
+/// The files are named `frame_000.dot`, `frame_001.dot`, …, where the first frame shows the
+/// graph before any decision. Each frame colors the clusters so far like
+/// [`colored_graph`](fn.colored_graph.html).
+/// Returns the number of written frames.
+pub fn write_animation_frames(graph: &DotGraph, params: &ClusterParams, dir: &Path) -> io::Result<usize> {
+    let (_, log) = greedy_cluster_with_log(graph, params);
+    let mut clusters: Vec<(NodeIndex, HashSet<NodeIndex>)> = Vec::new();
+    for frame in 0..=log.decisions.len() {
+        if frame > 0 {
+            let decision = log.decisions[frame - 1];
+            match clusters.get_mut(decision.cluster) {
+                Some((_, nodes)) => { nodes.insert(decision.node); }
+                None => clusters.push((decision.node, Some(decision.node).into_iter().collect())),
+            }
+        }
+        let clustering = Clustering {
+            clusters: clusters.iter()
+                .map(|(seed, nodes)| Cluster::with_params(*seed, nodes.clone(), graph, params))
+                .collect(),
+        };
+        let mut file = io::BufWriter::new(File::create(dir.join(format!("frame_{:03}.dot", frame)))?);
+        colored_graph(&clustering, graph).write(&mut file)?;
+        file.flush()?;
+    }
+    Ok(log.decisions.len() + 1)
+}
+
+/// Partitions the whole graph into exactly `k` clusters.
+///
+/// The greedy search grows the first `k` clusters like [`greedy_cluster`](fn.greedy_cluster.html),
+/// but doesn't start any further clusters. Instead, all remaining nodes are added to the
+/// existing cluster whose cut rises the least, i.e., to which they have the most edges, starting
+/// with the nodes most strongly connected to any cluster. Nodes pinned together are added
+/// together. `params.max_cluster_size` only limits the greedy search, and `params.objective`
+/// isn't applied.
+///
+/// If there are fewer than `k` clusterable nodes, the greedy search runs out of nodes before
+/// starting all clusters. The result has `k` entries regardless, where the clusters which
+/// couldn't be started are empty and come last.
+pub fn cluster_k(graph: &DotGraph, k: usize, params: &ClusterParams) -> Vec<HashSet<NodeIndex>> {
+    let mut node_set = clusterable_nodes(graph, params);
+    let pins = Pins::new(graph, params);
+    let mut seeds = seed_nodes(graph, params).into_iter();
+    let mut clusters = Vec::new();
+    while clusters.len() < k {
+        let seed = match seeds.by_ref().find(|seed| node_set.contains(seed)) {
+            Some(seed) => seed,
+            None => match next_seed(graph, params.seed_strategy, &node_set) {
+                Some(seed) => seed,
+                None => break,
+            },
+        };
+        let cluster = pins.group(seed, &node_set);
+        node_set.retain(|node_idx| !cluster.contains(node_idx));
+        info!("starting with {} (score: {:?})", graph[seed].id, score_with_params(&cluster, graph, params));
+        let cluster = grow(cluster, graph, params, &pins, &mut node_set, params.max_cluster_size,
+            &mut DecisionLog::default(), clusters.len());
+        clusters.push(cluster);
+    }
+
+    let edges_to = |node_idx: NodeIndex, cluster: &HashSet<NodeIndex>| {
+        graph.neighbors_undirected(node_idx).filter(|neighbor_idx| cluster.contains(neighbor_idx)).count()
+    };
+    loop {
+        // (edges to the cluster, node, cluster), preferring lower nodes and clusters on ties
+        let best = node_set.iter()
+            .flat_map(|&node_idx| clusters.iter().enumerate().map(move |(i, cluster)| (node_idx, i, cluster)))
+            .map(|(node_idx, i, cluster)| (edges_to(node_idx, cluster), node_idx, i))
+            .max_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)).then(b.2.cmp(&a.2)));
+        let (_, node_idx, i) = match best {
+            Some(best) => best,
+            None => break,
+        };
+        debug!("assigning {} to cluster {}", graph[node_idx].id, i);
+        let group = pins.group(node_idx, &node_set);
+        node_set.retain(|node_idx| !group.contains(node_idx));
+        clusters[i].extend(group);
+    }
+    clusters.resize(k, HashSet::new());
+    clusters
+}
+
+/// Returns for each clusterable item the cluster the greedy search grows when seeded from that
+/// item alone, with all other clusterable items still available.
+///
+/// The candidate clusters are independent of each other and may overlap, which helps to spot
+/// natural groupings of the graph.
+pub fn cluster_around_each(graph: &DotGraph, params: &ClusterParams) -> HashMap<NodeIndex, HashSet<NodeIndex>> {
+    let node_set = clusterable_nodes(graph, params);
+    let pins = Pins::new(graph, params);
+    node_set.iter()
+        .map(|&seed| (seed, grow_around(seed, graph, params, &pins, &node_set)))
+        .collect()
+}
+
+/// Returns the cluster the greedy search grows when seeded from `seed` alone, with all other
+/// clusterable items still available.
+///
+/// The seed is part of the cluster even if it's excluded from clustering.
+pub fn cluster_around(seed: NodeIndex, graph: &DotGraph, params: &ClusterParams) -> HashSet<NodeIndex> {
+    grow_around(seed, graph, params, &Pins::new(graph, params), &clusterable_nodes(graph, params))
+}
+
+fn grow_around(seed: NodeIndex, graph: &DotGraph, params: &ClusterParams, pins: &Pins, node_set: &HashSet<NodeIndex>) -> HashSet<NodeIndex> {
+    let cluster = pins.group(seed, node_set);
+    let mut remaining: HashSet<_> = node_set.difference(&cluster).copied().collect();
+    grow(cluster, graph, params, pins, &mut remaining, params.max_cluster_size, &mut DecisionLog::default(), 0)
+}
+
+/// Partitions the graph into clusters, starting from the partition of a previous run.
+///
+/// The previous clustering is mapped onto the current graph by node ids, dropping ids which don't
+/// exist anymore.
+/// Each previous cluster keeps all of its nodes and is grown further into nodes which aren't part
+/// of any previous cluster, e.g. because they were added to the graph since.
+/// Nodes still left afterwards are clustered as usual.
+/// This keeps the clustering stable across small changes of the graph.
+pub fn greedy_cluster_warm(graph: &DotGraph, prev: &Clustering, params: &ClusterParams) -> Clustering {
+    let mut node_set = clusterable_nodes(graph, params);
+    let pins = Pins::new(graph, params);
+    let mut warm_clusters = Vec::new();
+    for prev_cluster in &prev.clusters {
+        let nodes: HashSet<_> = prev_cluster.ids.iter()
+            .filter_map(|id| graph.id_map().get(id).copied())
+            .filter(|node_idx| node_set.contains(node_idx))
+            .collect();
+        let seed = match graph.id_map().get(&prev_cluster.seed) {
+            Some(seed) if nodes.contains(seed) => *seed,
+            _ => match nodes.iter().min() {
+                Some(&seed) => seed,
+                None => continue,
+            },
+        };
+        let nodes = if params.require_connected { connected_component(seed, &nodes, graph) } else { nodes };
+        let nodes: HashSet<_> = nodes.iter().flat_map(|&node_idx| pins.group(node_idx, &node_set)).collect();
+        node_set.retain(|node_idx| !nodes.contains(node_idx));
+        warm_clusters.push((seed, nodes));
+    }
+
+    let mut clustering = Clustering::default();
+    let mut log = DecisionLog::default();
+    for (seed, nodes) in warm_clusters {
+        info!("continuing with {} (score: {:?})", graph[seed].id, score_with_params(&nodes, graph, params));
+        let cluster = grow(nodes, graph, params, &pins, &mut node_set, params.max_cluster_size, &mut log, clustering.clusters.len());
+        clustering.clusters.push(Cluster::with_params(seed, cluster, graph, params));
+    }
+    cluster_remaining(graph, params, &pins, &mut node_set, &mut clustering, params.max_cluster_size, None, &mut log);
+    apply_objective(clustering, graph, params, params.max_cluster_size)
+}
+
+/// Refines the clustering of the greedy search according to `params.objective`.
+fn apply_objective(clustering: Clustering, graph: &DotGraph, params: &ClusterParams, max_cluster_size: Option<usize>) -> Clustering {
+    match params.objective {
+        Objective::TotalCut => clustering,
+        Objective::MinimaxCut => minimax_cut_with_max_size(&clustering, graph, params, max_cluster_size),
+    }
+}
+
+/// Returns all nodes which may be part of a cluster.
+fn clusterable_nodes(graph: &DotGraph, params: &ClusterParams) -> HashSet<NodeIndex> {
+    let mut node_set: HashSet<_> = graph.node_indices().collect();
+    for id in &params.excludes {
+        match graph.id_map().get(id.as_str()) {
+            Some(node_idx) => { node_set.remove(node_idx); }
+            None => warn!("ignoring unknown exclude {:?}", id),
+        }
+    }
+    if let Some(include) = &params.include {
+        node_set.retain(|&node_idx| include(&graph[node_idx]));
+    }
+    if let Some(universe) = &params.universe {
+        node_set.retain(|node_idx| universe.contains(node_idx));
+    }
+    if params.model == GraphModel::Bipartite {
+        node_set.retain(|&node_idx| !is_item(graph, node_idx));
+    }
+    node_set
+}
+
+/// Returns the nodes of the seeds in order, skipping unknown ids with a warning.
+fn seed_nodes(graph: &DotGraph, params: &ClusterParams) -> Vec<NodeIndex> {
+    let mut seeds = Vec::new();
+    for id in &params.seeds {
+        match graph.id_map().get(id.as_str()) {
+            Some(&node_idx) => seeds.push(node_idx),
+            None => warn!("ignoring unknown seed {:?}", id),
+        }
+    }
+    seeds
+}
+
+/// Grows clusters from the seeds and the remaining nodes of `node_set` until all nodes are
+/// part of a cluster, recording all decisions in `log`.
+#[allow(clippy::too_many_arguments)]
+fn cluster_remaining(graph: &DotGraph, params: &ClusterParams, pins: &Pins, node_set: &mut HashSet<NodeIndex>,
+                     clustering: &mut Clustering, max_cluster_size: Option<usize>,
+                     on_cluster_complete: Option<ClusterCallback>, log: &mut DecisionLog) {
+    let mut seeds = seed_nodes(graph, params).into_iter();
+
+    loop {
+        let seed = match seeds.by_ref().find(|seed| node_set.contains(seed)) {
+            Some(seed) => seed,
+            None => match next_seed(graph, params.seed_strategy, node_set) {
+                Some(seed) => seed,
+                None => break,
+            },
+        };
+        let cluster = pins.group(seed, node_set);
+        node_set.retain(|node_idx| !cluster.contains(node_idx));
+        let score = score_with_params(&cluster, graph, params);
+        info!("starting with {} (score: {:?})", graph[seed].id, score);
+        log.record(clustering.clusters.len(), &cluster, seed, None, score);
+        let cluster = grow(cluster, graph, params, pins, node_set, max_cluster_size, log, clustering.clusters.len());
+        let cluster = Cluster::with_params(seed, cluster, graph, params);
+        if let Some(on_cluster_complete) = on_cluster_complete {
+            on_cluster_complete(&cluster.nodes, cluster.score);
+        }
+        clustering.clusters.push(cluster);
+    }
+}
+
+/// Returns the node of `node_set` to grow the next cluster from according to the strategy.
+fn next_seed(graph: &DotGraph, strategy: SeedStrategy, node_set: &HashSet<NodeIndex>) -> Option<NodeIndex> {
+    match strategy {
+        SeedStrategy::NodeIndex => node_set.iter().min().copied(),
+        SeedStrategy::Importance => {
+            let importance = |node_idx: NodeIndex| graph[node_idx].attribute_f64("importance").unwrap_or(0.0);
+            let degree = |node_idx: NodeIndex| graph.neighbors_undirected(node_idx).count();
+            node_set.iter()
+                .copied()
+                .max_by(|&a, &b| importance(a).total_cmp(&importance(b))
+                    .then(degree(a).cmp(&degree(b)))
+                    .then(b.cmp(&a)))
+        }
+    }
+}
+
+/// Returns the cost of the cluster with the given score, which the greedy search minimizes.
+///
+/// This is the [`weighted`](struct.ClusterScore.html#method.weighted) score, unless a
+/// `score_script` is set.
+/// If the `score_script` fails to evaluate, e.g. because of a division by zero, the weighted
+/// score is used instead.
+#[cfg_attr(not(feature = "scripting"), allow(unused_variables))]
+fn cost(cluster: &HashSet<NodeIndex>, score: ClusterScore, graph: &DotGraph, params: &ClusterParams) -> f64 {
+    #[cfg(feature = "scripting")]
+    {
+        if let Some(script) = &params.score_script {
+            match script.eval(score, cluster.len(), category_count(cluster, graph)) {
+                Ok(cost) => return cost,
+                Err(e) => warn!("{}, using the weighted score instead", e),
+            }
+        }
+    }
+    score.weighted(params)
+}
+
+/// Grows the given cluster, only adding nodes from `node_set`.
+///
+/// A node is added if it doesn't increase the cost of the cluster.
+/// Pinned nodes are only added together with all other nodes of `node_set` pinned to them.
+/// All nodes added to the cluster are removed from `node_set` and recorded in `log` as part of
+/// the cluster with index `cluster_idx`.
+/// The cluster stops growing once it reaches `max_cluster_size`.
+#[allow(clippy::too_many_arguments)]
+fn grow(mut current_cluster: HashSet<NodeIndex>, graph: &DotGraph, params: &ClusterParams, pins: &Pins,
+        node_set: &mut HashSet<NodeIndex>, max_cluster_size: Option<usize>, log: &mut DecisionLog,
+        cluster_idx: usize) -> HashSet<NodeIndex> {
+
+    loop {
+        let mut scores = Vec::new();
+        for node_idx in subgraph_neighbors_with_duplicates(&current_cluster, graph, params.model) {
+            if !node_set.contains(&node_idx) {
+                continue;
+            }
+            let mut cluster = current_cluster.clone();
+            cluster.extend(pins.group(node_idx, node_set));
+            let score = score_with_params(&cluster, graph, params);
+            scores.push((node_idx, score, cost(&cluster, score, graph, params)));
+        }
+
+        let current = score_with_params(&current_cluster, graph, params);
+        let current_cost = cost(&current_cluster, current, graph, params);
+
+        let mut added_something = false;
+
+        for &(node_idx, candidate, candidate_cost) in &scores {
+            if max_cluster_size.is_some_and(|max| current_cluster.len() >= max) {
+                return current_cluster;
+            }
+            if current_cluster.contains(&node_idx) {
+                continue;
+            }
+            let group = pins.group(node_idx, node_set);
+            if group.len() > 1 && max_cluster_size.is_some_and(|max| current_cluster.len() + group.len() > max) {
+                continue;
+            }
+            if candidate_cost <= current_cost {
+                info!("    adding {} (score: {:?})", graph[node_idx].id, candidate);
+                let parent = graph.neighbors_undirected(node_idx).filter(|neighbor_idx| current_cluster.contains(neighbor_idx)).min();
+                log.record(cluster_idx, &group, node_idx, parent, candidate);
+                node_set.retain(|node_idx| !group.contains(node_idx));
+                current_cluster.extend(group);
+                added_something = true;
+            }
+        }
+        debug!("    ---------");
+
+        if !added_something {
+            let lowest = scores.iter().map(|&(_, _, cost)| cost).fold(f64::INFINITY, f64::min);
+            for &(node_idx, _, cost) in &scores {
+                if cost <= lowest {
+                    debug!("    lowest would have been {} (score: {:?})", graph[node_idx].id, cost);
+                }
+            }
+            return current_cluster;
+        }
+    }
+}
+
+#[test]
+fn test_sort_by_size() {
+    let graph = crate::graphviz::parse("digraph { a -> b; c -> d -> e; f }");
+    let mut clustering = Clustering {
+        clusters: vec![cluster_of(&graph, &["f"]), cluster_of(&graph, &["c", "d", "e"]), cluster_of(&graph, &["a", "b"])],
+    };
+    clustering.sort_by(ClusterOrder::Size);
+    let sizes: Vec<_> = clustering.clusters.iter().map(|c| c.nodes.len()).collect();
+    assert_eq!(sizes, vec![3, 2, 1]);
+    assert_eq!(clustering.clusters[0].seed, "c");
+
+    let mut clustering = greedy_cluster(&graph, &ClusterParams::default());
+    clustering.sort_by(ClusterOrder::Size);
+    let sizes: Vec<_> = clustering.clusters.iter().map(|c| c.nodes.len()).collect();
+    assert!(sizes.windows(2).all(|w| w[0] >= w[1]));
+    assert_eq!(sizes.iter().sum::<usize>(), graph.node_count());
+}
+
+#[test]
+fn test_unknown_seeds() {
+    let graph = crate::graphviz::parse("digraph { a -> b; c }");
+    let params = ClusterParams { seeds: vec!["unknown".to_string(), "c".to_string()], ..ClusterParams::default() };
+    let clustering = greedy_cluster(&graph, &params);
+    assert_eq!(clustering.clusters[0].seed, "c");
+    assert_eq!(clustering.clusters.len(), 2);
+}
+
+#[test]
+fn test_raw_resource_footprint() {
+    let graph = crate::graphviz::parse("digraph { a -> b; a -> c; a -> r4; b -> r1; b -> r2; c -> r2; c -> r3; x -> r5 }");
+    let id_map = graph.id_map();
+    let cluster = Some(id_map["a"]).into_iter().collect();
+    let footprint: HashSet<_> = ["r1", "r2", "r3", "r4"].iter().map(|id| id_map[*id]).collect();
+    assert_eq!(raw_resource_footprint(&cluster, &graph), footprint);
+}
+
+#[test]
+fn test_sweep() {
+    let graph = crate::graphviz::parse("digraph { a -> b -> c -> d; c -> e; f -> e; f -> g -> h }");
+    let params = ClusterParams {
+        seeds: vec!["a".to_string()],
+        ..ClusterParams::default()
+    };
+    let costs = sweep(&graph, &[1, 2, 4, 8], &params);
+    assert_eq!(costs.iter().map(|&(size, _)| size).collect::<Vec<_>>(), vec![1, 2, 4, 8]);
+    assert_eq!(costs[0].1, graph.edge_count() as f64);
+    assert!(costs.windows(2).all(|w| w[0].1 >= w[1].1), "{:?}", costs);
+    assert!(costs[3].1 < costs[0].1);
+}
+
+#[test]
+fn test_high_fanin_items() {
+    let mut dot = "digraph { iron_plate -> iron_ore; gear -> iron_plate; ".to_string();
+    for i in 0..9 {
+        dot += &format!("item{} -> iron_plate; item{} -> gear; ", i, i);
+    }
+    dot += "}";
+    let graph = crate::graphviz::parse(&dot);
+    let id_map = graph.id_map();
+    assert_eq!(high_fanin_items(&graph, 5), vec![id_map["iron_plate"], id_map["gear"]]);
+    assert_eq!(high_fanin_items(&graph, 9), vec![id_map["iron_plate"]]);
+
+    let params = ClusterParams {
+        excludes: vec!["iron_plate".to_string(), "copper_plate".to_string()],
+        ..ClusterParams::default()
+    };
+    // the unknown copper_plate is ignored
+    let clustering = greedy_cluster(&graph, &params);
+    assert!(!clustering.assignment().contains_key(&id_map["iron_plate"]));
+    assert_eq!(clustering.assignment().len(), graph.node_count() - 1);
+}
+
+#[test]
+fn test_density() {
+    let graph = crate::graphviz::parse("digraph { a -> b -> c -> a; b -> a; c -> b; a -> c; d -> e -> f; f -> x }");
+    let id_map = graph.id_map();
+    let cluster = |ids: &[&str]| ids.iter().map(|id| id_map[*id]).collect::<HashSet<_>>();
+    assert_eq!(density(&cluster(&["a", "b", "c"]), &graph), 1.0);
+    assert_eq!(density(&cluster(&["d", "e", "f"]), &graph), 2.0 / 6.0);
+    assert_eq!(density(&cluster(&["d"]), &graph), 0.0);
+
+    let graph = crate::graphviz::parse("digraph { a -> b; a -> b; a -> b; b -> a; a -> a }");
+    let id_map = graph.id_map();
+    let cluster = ["a", "b"].iter().map(|id| id_map[*id]).collect();
+    assert_eq!(density(&cluster, &graph), 1.0);
+
+    let graph = crate::graphviz::parse("graph { a -- b -- c }");
+    let id_map = graph.id_map();
+    let cluster = ["a", "b", "c"].iter().map(|id| id_map[*id]).collect();
+    assert_eq!(density(&cluster, &graph), 2.0 / 3.0);
+
+    let graph = crate::graphviz::parse("graph { a -- b; b -- a }");
+    let id_map = graph.id_map();
+    let cluster = ["a", "b"].iter().map(|id| id_map[*id]).collect();
+    assert_eq!(density(&cluster, &graph), 1.0);
+}
+
+#[test]
+fn test_greedy_cluster_warm() {
+    let graph = crate::graphviz::parse("digraph { a -> b -> c; a -> d; e -> f -> g; h -> g }");
+    let prev = greedy_cluster(&graph, &ClusterParams::default());
+
+    // the graph changed: a new consumer of `b`, while `h` vanished
+    let graph = crate::graphviz::parse("digraph { a -> b -> c; a -> d; e -> f -> g; x -> b }");
+    let warm = greedy_cluster_warm(&graph, &prev, &ClusterParams::default());
+    assert_eq!(warm.assignment().len(), graph.node_count());
+    for (prev_cluster, cluster) in prev.clusters.iter().zip(&warm.clusters) {
+        assert_eq!(prev_cluster.seed, cluster.seed);
+        let expected: BTreeSet<_> = prev_cluster.ids.iter().filter(|id| *id != "h").cloned().collect();
+        assert!(cluster.ids.is_superset(&expected), "{:?} moved to {:?}", prev_cluster.ids, cluster.ids);
+    }
+}
+
+#[test]
+fn test_include() {
+    let graph = crate::graphviz::parse(r#"digraph {
+        a [tier=2] b [tier=2] c [tier=2] x [tier=1] y [tier=1]
+        a -> b -> x; a -> c -> y; x -> y
+    }"#);
+    let params = ClusterParams {
+        include: Some(Box::new(|node: &Node| node.attributes.get("tier").map(|tier| tier == "2").unwrap_or(false))),
+        ..ClusterParams::default()
+    };
+    let clustering = greedy_cluster(&graph, &params);
+    let id_map = graph.id_map();
+    let assignment = clustering.assignment();
+    let mut clustered: Vec<_> = assignment.keys().map(|&node_idx| &*graph[node_idx].id).collect();
+    clustered.sort();
+    assert_eq!(clustered, ["a", "b", "c"]);
+    let cluster = &clustering.clusters[assignment[&id_map["a"]]];
+    assert_eq!(cluster.nodes.len(), 3);
+    assert_eq!(cluster.score.num_deps, 2);
+}
+
+#[test]
+fn test_minimax_cut() {
+    let graph = crate::graphviz::parse("digraph {
+        a -> b; a -> c; b -> d; c -> d; d -> e; e -> f; e -> g; f -> h; g -> h; i -> h; j -> h; k -> a
+    }");
+    let total = greedy_cluster(&graph, &ClusterParams {
+        max_cluster_size: Some(2),
+        ..ClusterParams::default()
+    });
+    let minimax = greedy_cluster(&graph, &ClusterParams {
+        max_cluster_size: Some(2),
+        objective: Objective::MinimaxCut,
+        ..ClusterParams::default()
+    });
+    assert_ne!(total, minimax);
+    assert_eq!(total.clusters.len(), minimax.clusters.len());
+    assert_eq!(minimax.assignment().len(), graph.node_count());
+    assert!(minimax.clusters.iter().all(|cluster| cluster.nodes.len() <= 2));
+    assert!(peak_cut(&minimax, &graph) < peak_cut(&total, &graph));
+}
+
+/// Creates a cluster of the nodes with the given ids, seeded by the first one.
+#[cfg(test)]
+pub(crate) fn cluster_of(graph: &DotGraph, ids: &[&str]) -> Cluster {
+    let id_map = graph.id_map();
+    Cluster::new(id_map[ids[0]], ids.iter().map(|id| id_map[*id]).collect(), graph)
+}
+
+#[test]
+fn test_require_connected() {
+    let graph = crate::graphviz::parse("digraph { s -> m -> t; m -> b1; m -> b2; m -> b3; b0 -> b1; b0 -> b2; b0 -> b3 }");
+    let clustering = Clustering {
+        clusters: vec![cluster_of(&graph, &["s", "m", "t"]), cluster_of(&graph, &["b0", "b1", "b2", "b3"])],
+    };
+    assert_connected(&clustering, &graph);
+
+    // moving the bridge `m` lowers the peak cut, but disconnects `s` and `t`
+    let mut params = ClusterParams {
+        max_cluster_size: Some(5),
+        ..ClusterParams::default()
+    };
+    let refined = minimax_cut(&clustering, &graph, &params);
+    assert!(!is_connected(&refined.clusters[0].nodes, &graph));
+
+    params.require_connected = true;
+    let refined = minimax_cut(&clustering, &graph, &params);
+    assert_connected(&refined, &graph);
+
+    // warm-starting after `m` vanished splits `t` off the cluster of `s`
+    let graph = crate::graphviz::parse("digraph { s -> x; t -> y; b0 -> b1; b0 -> b2; b0 -> b3 }");
+    let warm = greedy_cluster_warm(&graph, &clustering, &params);
+    assert_connected(&warm, &graph);
+    assert!(!warm.clusters[0].ids.contains("t"));
+}
+
+#[test]
+fn test_deserialize_params() {
+    let params: ClusterParams = toml::from_str(r#"
+        seeds = ["sulfuric-acid", "plastic-bar"]
+        excludes = ["iron-plate"]
+        max_cluster_size = 10
+        objective = "minimax_cut"
+    "#).unwrap();
+    assert_eq!(params.seeds, ["sulfuric-acid", "plastic-bar"]);
+    assert_eq!(params.excludes, ["iron-plate"]);
+    assert_eq!(params.max_cluster_size, Some(10));
+    assert_eq!(params.objective, Objective::MinimaxCut);
+    assert!(!params.require_connected);
+    assert!(params.include.is_none());
+
+    let params: ClusterParams = toml::from_str("").unwrap();
+    assert!(params.seeds.is_empty());
+    assert_eq!(params.objective, Objective::TotalCut);
+    assert!(toml::from_str::<ClusterParams>("seed = \"a\"").is_err());
+}
+
+#[test]
+fn test_score_terminal_product() {
+    let graph = crate::graphviz::parse("digraph { engine -> gear -> plate -> ore; pipe -> plate; circuit -> gear }");
+    let id_map = graph.id_map();
+    // `gear` isn't consumed inside the cluster, but by `engine` and `circuit` outside of it
+    let cluster: HashSet<_> = ["gear", "plate"].iter().map(|id| id_map[*id]).collect();
+    assert_eq!(score(&cluster, &graph), ClusterScore { num_deps: 1, num_outputs: 1 });
+    // `plate` is consumed by `pipe`, but only passes through from `ore`
+    let cluster: HashSet<_> = ["plate"].iter().map(|id| id_map[*id]).collect();
+    assert_eq!(score(&cluster, &graph), ClusterScore { num_deps: 1, num_outputs: 0 });
+}
+
+#[test]
+fn test_write_clustered_dot() {
+    let graph = crate::graphviz::parse("digraph { a -> b; c -> d; b -> d }");
+    let clustering = Clustering {
+        clusters: vec![cluster_of(&graph, &["a", "b"]), cluster_of(&graph, &["c"])],
+    };
+    let mut written = Vec::new();
+    write_clustered_dot(&clustering, &graph, &mut written).unwrap();
+    let reparsed = crate::graphviz::parse(std::str::from_utf8(&written).unwrap());
+
+    let members = |subgraph: &Subgraph| {
+        let mut ids: Vec<_> = subgraph.nodes.iter().map(|&node_idx| reparsed[node_idx].id.as_str()).collect();
+        ids.sort();
+        ids
+    };
+    assert_eq!(reparsed.node_count(), 4);
+    assert_eq!(reparsed.edge_count(), 3);
+    assert_eq!(reparsed.subgraphs.len(), 2);
+    let cluster_0 = &reparsed.subgraphs[0];
+    assert_eq!(cluster_0.id, "cluster_0");
+    assert_eq!(cluster_0.attributes["label"], "a (2 items)");
+    assert_eq!(members(cluster_0), ["a", "b"]);
+    let cluster_1 = &reparsed.subgraphs[1];
+    assert_eq!(cluster_1.id, "cluster_1");
+    assert_eq!(members(cluster_1), ["c"]);
+    // unclustered nodes are written at the top level
+    assert!(reparsed.id_map().contains_key("d"));
+}
+
+#[test]
+fn test_dir_hints() {
+    let digraph = crate::graphviz::parse("digraph { a -> b; c -> b; d -> c; c -> e }");
+    let undirected = crate::graphviz::parse(r#"graph { a -- b [dir=forward]; b -- c [dir=back]; d -- c; c -- e [dir=both] }"#);
+    let params = ClusterParams {
+        dir_hints: true,
+        ..ClusterParams::default()
+    };
+    for ids in &[&["b", "c"][..], &["a"], &["b"], &["c", "d"], &["a", "b", "c"]] {
+        let subgraph = |graph: &DotGraph| -> HashSet<_> { ids.iter().map(|id| graph.id_map()[*id]).collect() };
+        let expected = score(&subgraph(&digraph), &digraph);
+        assert_eq!(score_with_params(&subgraph(&undirected), &undirected, &params).num_deps, expected.num_deps, "{:?}", ids);
+    }
+    // `c -- e [dir=both]` makes `e` both a dependency and a consumer of `c`
+    let c: HashSet<_> = Some(undirected.id_map()["c"]).into_iter().collect();
+    assert_eq!(score_with_params(&c, &undirected, &params).num_deps, 2);
+    assert_eq!(score(&c, &undirected).num_deps, 1);
+}
+
+#[test]
+fn test_dependency_cluster_map() {
+    let graph = crate::graphviz::parse("digraph { circuit -> cable -> copper; circuit -> iron; circuit -> plastic }");
+    let id_map = graph.id_map();
+    let clustering = Clustering {
+        clusters: vec![cluster_of(&graph, &["circuit", "cable"]), cluster_of(&graph, &["copper", "iron"])],
+    };
+    let map = dependency_cluster_map(id_map["circuit"], &clustering, &graph);
+    assert_eq!(map.len(), 3);
+    assert_eq!(map[&id_map["cable"]], Some(0));
+    assert_eq!(map[&id_map["iron"]], Some(1));
+    assert_eq!(map[&id_map["plastic"]], None);
+}
+
+#[test]
+fn test_inter_cluster_belts() {
+    let graph = crate::graphviz::parse(r#"digraph {
+        circuit -> cable [amount=30]; circuit -> iron [amount=10]; cable -> copper [amount=15]; copper -> ore [amount=20]
+    }"#);
+    let clustering = Clustering {
+        clusters: vec![cluster_of(&graph, &["circuit"]), cluster_of(&graph, &["cable", "iron"]), cluster_of(&graph, &["copper"])],
+    };
+    let flows = inter_cluster_flows(&clustering, &graph);
+    assert_eq!(flows.len(), 2);
+    assert_eq!(flows[&(1, 0)], 40.0);
+    assert_eq!(flows[&(2, 1)], 15.0);
+
+    let belts = inter_cluster_belts(&clustering, &graph, 15.0).unwrap();
+    assert_eq!(belts[&(1, 0)], 3);
+    assert_eq!(belts[&(2, 1)], 1);
+    assert_eq!(inter_cluster_belts(&clustering, &graph, 0.0), None);
+    assert_eq!(factorio::belts_needed(0.0, 15.0), Some(0));
+    assert_eq!(factorio::belts_needed(10.0, 0.0), None);
+    assert_eq!(factorio::belts_needed(10.0, -15.0), None);
+    assert_eq!(factorio::belts_needed(10.0, f64::NAN), None);
+}
+
+#[test]
+fn test_write_clustered_dot_crossing_edges() {
+    let graph = crate::graphviz::parse("digraph { a -> b; b -> c }");
+    let id_map = graph.id_map();
+    let clustering = Clustering {
+        clusters: vec![cluster_of(&graph, &["a", "b"]), cluster_of(&graph, &["c"])],
+    };
+    let crossing = edges_crossing(&clustering, &graph);
+    assert_eq!(crossing, vec![graph.find_edge(id_map["b"], id_map["c"]).unwrap()]);
+
+    let mut written = Vec::new();
+    write_clustered_dot(&clustering, &graph, &mut written).unwrap();
+    let written = String::from_utf8(written).unwrap();
+    let edge = |head: &str| {
+        let start = written.find(head).unwrap();
+        let end = start + written[start..].find("\n  ]").unwrap();
+        &written[start..end]
+    };
+    assert!(edge("\"b\" -> \"c\"[").contains("color = \"red\""));
+    assert!(edge("\"b\" -> \"c\"[").contains("penwidth = \"2\""));
+    assert!(!edge("\"a\" -> \"b\"[").contains("color"));
+    assert!(!edge("\"a\" -> \"b\"[").contains("penwidth"));
+}
+
+#[test]
+fn test_colored_graph() {
+    let graph = crate::graphviz::parse(r#"digraph { a [label="Iron Plate" style=dashed]; b; c; a -> b -> c }"#);
+    let id_map = graph.id_map();
+    let clustering = Clustering {
+        clusters: vec![cluster_of(&graph, &["a"]), cluster_of(&graph, &["b"])],
+    };
+    let colored = colored_graph(&clustering, &graph);
+    let a = &colored[id_map["a"]];
+    assert_eq!(a.attributes["label"], "Iron Plate");
+    assert_eq!(a.attributes["style"], "dashed,filled");
+    assert_eq!(a.attributes["fillcolor"], "1");
+    assert_eq!(colored[id_map["b"]].attributes["fillcolor"], "2");
+    assert!(colored[id_map["c"]].attributes.is_empty());
+    assert_eq!(colored.edge_count(), graph.edge_count());
+}
+
+#[test]
+fn test_best_internalization() {
+    // pulling in `plate` replaces three edges to it by one to `ore`, `gear` only replaces one edge
+    let graph = crate::graphviz::parse("digraph { a -> plate; b -> plate; c -> plate; plate -> ore; a -> gear; gear -> iron -> ore }");
+    let id_map = graph.id_map();
+    let cluster: HashSet<_> = ["a", "b", "c"].iter().map(|id| id_map[*id]).collect();
+    assert_eq!(score(&cluster, &graph).num_deps, 4);
+    let (node_idx, score) = best_internalization(&cluster, &graph).unwrap();
+    assert_eq!(node_idx, id_map["plate"]);
+    assert_eq!(score.num_deps, 2);
+
+    let cluster: HashSet<_> = ["gear"].iter().map(|id| id_map[*id]).collect();
+    assert_eq!(best_internalization(&cluster, &graph), None);
+}
+
+#[test]
+fn test_stability() {
+    let graph = crate::graphviz::parse("digraph { a -> b; c -> d }");
+    let clustering = |clusters: &[&[&str]]| Clustering {
+        clusters: clusters.iter()
+            .map(|ids| cluster_of(&graph, ids))
+            .collect(),
+    };
+    let old = clustering(&[&["a", "b"], &["c", "d"]]);
+    assert_eq!(stability(&old, &old.clone()), 1.0);
+    // cluster order doesn't matter
+    assert_eq!(stability(&old, &clustering(&[&["c", "d"], &["a", "b"]])), 1.0);
+
+    let together = clustering(&[&["a", "b", "c", "d"]]);
+    let apart = clustering(&[&["a"], &["b"], &["c"], &["d"]]);
+    assert_eq!(stability(&together, &apart), 0.0);
+    // a-b stays together, a-d and b-d stay apart, a-c, b-c and c-d change
+    assert_eq!(stability(&old, &clustering(&[&["a", "b", "c"], &["d"]])), 0.5);
+}
+
+#[test]
+fn test_seed_strategy_importance() {
+    let graph = crate::graphviz::parse(r#"digraph {
+        gear [importance=1]; science [importance=10]; rocket [importance=10]; plate
+        science -> gear -> plate; science -> plate; rocket -> plate
+    }"#);
+    let params = ClusterParams {
+        max_cluster_size: Some(1),
+        seed_strategy: SeedStrategy::Importance,
+        ..ClusterParams::default()
+    };
+    let seeds: Vec<_> = greedy_cluster(&graph, &params).clusters.into_iter().map(|cluster| cluster.seed).collect();
+    // `science` and `rocket` are equally important, but `science` has the higher degree
+    assert_eq!(seeds, ["science", "rocket", "gear", "plate"]);
+
+    let seeds: Vec<_> = greedy_cluster(&graph, &ClusterParams { max_cluster_size: Some(1), ..ClusterParams::default() })
+        .clusters.into_iter().map(|cluster| cluster.seed).collect();
+    assert_eq!(seeds, ["gear", "science", "rocket", "plate"]);
+}
+
+#[test]
+fn test_condense() {
+    let graph = crate::graphviz::parse("digraph { a -> b [amount=2]; a -> c [amount=3]; b -> d; c -> d; d -> e }");
+    let clustering = Clustering {
+        clusters: vec![cluster_of(&graph, &["a"]), cluster_of(&graph, &["b", "c", "d", "e"])],
+    };
+    let condensed = condense(&clustering, &graph);
+    assert_eq!(condensed.node_count(), 2);
+    assert_eq!(condensed[condensed.id_map()["cluster_1"]].attributes["label"], "b (4 items)");
+    assert_eq!(condensed.edge_count(), 1);
+    let edge = condensed.find_edge(condensed.id_map()["cluster_0"], condensed.id_map()["cluster_1"]).unwrap();
+    assert_eq!(condensed[edge].amount(), 5.0);
+}
+
+#[test]
+fn test_rank_external_inputs() {
+    let graph = crate::graphviz::parse(r#"digraph {
+        oil [import_cost=5]
+        plastic -> gas [amount=2]; plastic -> coal; sulfur -> gas; sulfur -> water [amount=3]; gas -> oil
+    }"#);
+    let id_map = graph.id_map();
+    let cluster: HashSet<_> = ["plastic", "sulfur"].iter().map(|id| id_map[*id]).collect();
+    let ranking = rank_external_inputs(&cluster, &graph, import_cost_attribute(&graph));
+    assert_eq!(ranking, vec![(id_map["gas"], 3.0), (id_map["water"], 3.0), (id_map["coal"], 1.0)]);
+
+    let cost = |node_idx| if node_idx == id_map["coal"] { 10.0 } else { 1.0 };
+    let ranking = rank_external_inputs(&cluster, &graph, cost);
+    let order: Vec<_> = ranking.iter().map(|&(node_idx, _)| &*graph[node_idx].id).collect();
+    assert_eq!(order, ["coal", "gas", "water"]);
+
+    let cluster: HashSet<_> = ["gas"].iter().map(|id| id_map[*id]).collect();
+    assert_eq!(rank_external_inputs(&cluster, &graph, import_cost_attribute(&graph)), vec![(id_map["oil"], 5.0)]);
+}
+
+#[test]
+fn test_greedy_cluster_with_callback() {
+    use std::cell::RefCell;
+
+    let graph = crate::graphviz::parse("digraph { a -> b -> c; d -> e; f -> c; g }");
+    let completed = RefCell::new(Vec::new());
+    let clustering = greedy_cluster_with_callback(&graph, &ClusterParams::default(), Some(&|nodes: &HashSet<NodeIndex>, score| {
+        completed.borrow_mut().push((nodes.clone(), score));
+    }));
+    let returned: Vec<_> = clustering.clusters.into_iter().map(|cluster| (cluster.nodes, cluster.score)).collect();
+    assert_eq!(completed.into_inner(), returned);
+}
+
+#[test]
+fn test_pin_subgraphs() {
+    // without pins, the greedy search puts c into the cluster of a and b, leaving d on its own
+    let graph = crate::graphviz::parse("digraph {
+        subgraph cluster_left { a; b }
+        subgraph cluster_right { c; d }
+        a -> b; a -> c; d -> b; d -> c
+    }");
+    let id_map = graph.id_map();
+    let clustering = greedy_cluster(&graph, &ClusterParams { max_cluster_size: Some(3), ..ClusterParams::default() });
+    let assignment = clustering.assignment();
+    assert_ne!(assignment[&id_map["c"]], assignment[&id_map["d"]]);
+
+    let params = ClusterParams { max_cluster_size: Some(3), pin_subgraphs: true, ..ClusterParams::default() };
+    let clustering = greedy_cluster(&graph, &params);
+    let assignment = clustering.assignment();
+    assert_eq!(assignment[&id_map["a"]], assignment[&id_map["b"]]);
+    assert_eq!(assignment[&id_map["c"]], assignment[&id_map["d"]]);
+}
+
+#[test]
+fn test_self_sufficiency() {
+    let graph = crate::graphviz::parse("digraph { a -> b; a -> c; b -> c; c -> ore }");
+    let id_map = graph.id_map();
+    let cluster = |ids: &[&str]| ids.iter().map(|id| id_map[*id]).collect::<HashSet<_>>();
+    assert_eq!(self_sufficiency(&cluster(&["a", "b", "c"]), &graph), 0.75);
+    assert_eq!(self_sufficiency(&cluster(&["ore"]), &graph), 1.0);
+    assert_eq!(self_sufficiency(&cluster(&["b"]), &graph), 0.0);
+}
+
+#[test]
+fn test_cluster_around_each() {
+    let graph = crate::graphviz::parse("digraph { a -> b -> c; a -> c; x -> y; c -> y }");
+    let id_map = graph.id_map();
+    let candidates = cluster_around_each(&graph, &ClusterParams::default());
+    assert_eq!(candidates.len(), graph.node_count());
+    let a = &candidates[&id_map["a"]];
+    assert!(a.contains(&id_map["b"]) && a.contains(&id_map["c"]));
+    assert!(candidates[&id_map["b"]].contains(&id_map["b"]));
+}
+
+#[test]
+fn test_score_weights() {
+    // adding b to a internalizes the dependency b, but turns a into an output consumed by y
+    let graph = crate::graphviz::parse("digraph { y -> a; y -> z; a -> b }");
+    let id_map = graph.id_map();
+    let params = |dep_weight, output_weight| ClusterParams {
+        seeds: vec!["a".to_string()],
+        dep_weight,
+        output_weight,
+        ..ClusterParams::default()
+    };
+    let cluster = [id_map["a"], id_map["b"]].iter().copied().collect();
+    assert_eq!(score(&cluster, &graph).weighted(&params(3.0, 1.0)), 1.0);
+
+    let assignment = greedy_cluster(&graph, &params(3.0, 1.0)).assignment();
+    assert_eq!(assignment[&id_map["a"]], assignment[&id_map["b"]]);
+    let assignment = greedy_cluster(&graph, &params(1.0, 3.0)).assignment();
+    assert_ne!(assignment[&id_map["a"]], assignment[&id_map["b"]]);
+
+    // adding r to a keeps a single dependency, but turns a into an output, which the weights
+    // make more expensive
+    let graph = crate::graphviz::parse("digraph { y -> a; y -> z; a -> r; r -> s }");
+    let id_map = graph.id_map();
+    let assignment = greedy_cluster(&graph, &params(1.0, 3.0)).assignment();
+    assert_ne!(assignment[&id_map["a"]], assignment[&id_map["r"]]);
+}
+
+#[cfg(feature = "scripting")]
+#[test]
+fn test_score_script() {
+    // same graph as in test_score_weights
+    let graph = crate::graphviz::parse("digraph { y -> a; y -> z; a -> b }");
+    let id_map = graph.id_map();
+    let params = |script: &str| ClusterParams {
+        seeds: vec!["a".to_string()],
+        score_script: Some(ScoreScript::compile(script).unwrap()),
+        ..ClusterParams::default()
+    };
+    let assignment = greedy_cluster(&graph, &params("num_deps * 2 + num_outputs")).assignment();
+    assert_eq!(assignment[&id_map["a"]], assignment[&id_map["b"]]);
+    let assignment = greedy_cluster(&graph, &params("num_deps + num_outputs * 3")).assignment();
+    assert_ne!(assignment[&id_map["a"]], assignment[&id_map["b"]]);
+    // floats work as well, and preferring large clusters merges everything
+    let assignment = greedy_cluster(&graph, &params("-1.5 * size")).assignment();
+    assert!(graph.node_indices().all(|node_idx| assignment[&node_idx] == assignment[&id_map["a"]]));
+
+    // invalid scripts are rejected when loading the parameters
+    assert!(ScoreScript::compile("num_deps +").is_err());
+    assert!(ScoreScript::compile("unknown * 2").is_err());
+    assert!(ScoreScript::compile(r#""cheap""#).is_err());
+    assert!(toml::from_str::<ClusterParams>(r#"score_script = "num_deps +""#).is_err());
+    let loaded: ClusterParams = toml::from_str(r#"score_script = "num_deps * 2""#).unwrap();
+    assert_eq!(loaded.score_script.unwrap().source(), "num_deps * 2");
+    // errors during the search fall back to the weighted score
+    let assignment = greedy_cluster(&graph, &params("num_deps / (size - 2)")).assignment();
+    assert_eq!(assignment.len(), graph.node_count());
+}
+
+#[test]
+fn test_category_count() {
+    let graph = crate::graphviz::parse("digraph {
+        plastic [category=chemistry]; sulfur [category=chemistry]; steel [category=smelting]; coal
+        plastic -> coal; sulfur -> coal; steel -> coal
+    }");
+    let id_map = graph.id_map();
+    let nodes: HashSet<_> = ["plastic", "sulfur", "steel"].iter().map(|id| id_map[*id]).collect();
+    assert_eq!(category_count(&nodes, &graph), 2);
+
+    let cluster = Cluster::new(id_map["plastic"], nodes, &graph);
+    let report = ClusterReport::new(&cluster, &graph);
+    assert_eq!(report.category_count, 2);
+    assert_eq!(report.size, 3);
+    assert_eq!(report.cut, 3.0);
+}
+
+#[test]
+fn test_shared_raw_resources() {
+    let graph = crate::graphviz::parse("digraph { gear -> plate -> ore; pipe -> plate2 -> ore; plate2 -> coal }");
+    let id_map = graph.id_map();
+    let clustering = Clustering { clusters: vec![cluster_of(&graph, &["gear", "plate"]), cluster_of(&graph, &["pipe"])] };
+    let shared = shared_raw_resources(&clustering, &graph);
+    assert_eq!(shared.len(), 1);
+    assert_eq!(shared[&id_map["ore"]], vec![0, 1]);
+}
+
+#[test]
+fn test_move_node_delta() {
+    let graph = crate::graphviz::parse("digraph { a -> b -> c -> d; c -> e; x -> d }");
+    let id_map = graph.id_map();
+    let nodes = |ids: &[&str]| ids.iter().map(|id| id_map[*id]).collect::<HashSet<_>>();
+    let from = Cluster::new(id_map["a"], nodes(&["a", "b", "c"]), &graph);
+    let to = Cluster::new(id_map["d"], nodes(&["d", "e"]), &graph);
+    let (from_score, to_score) = move_node_delta(id_map["c"], &from, &to, &graph);
+    assert_eq!(from_score, score(&nodes(&["a", "b"]), &graph));
+    assert_eq!(to_score, score(&nodes(&["c", "d", "e"]), &graph));
+    assert_ne!(from_score, from.score);
+    assert!(from.nodes.contains(&id_map["c"]));
+}
+
+#[test]
+fn test_boundary_edges() {
+    let graph = crate::graphviz::parse("digraph { a -> b; b -> c; c -> d; d -> x; a -> x }");
+    let id_map = graph.id_map();
+    let clustering = Clustering { clusters: vec![cluster_of(&graph, &["a", "b"]), cluster_of(&graph, &["c", "d"])] };
+    let boundary: Vec<_> = boundary_edges(&clustering, &graph).collect();
+    let edge = |a: &str, b: &str| graph.find_edge(id_map[a], id_map[b]).unwrap();
+    assert_eq!(boundary, vec![
+        BoundaryEdge { edge: edge("b", "c"), from_cluster: Some(0), to_cluster: Some(1), from_node: id_map["b"], to_node: id_map["c"] },
+        BoundaryEdge { edge: edge("d", "x"), from_cluster: Some(1), to_cluster: None, from_node: id_map["d"], to_node: id_map["x"] },
+        BoundaryEdge { edge: edge("a", "x"), from_cluster: Some(0), to_cluster: None, from_node: id_map["a"], to_node: id_map["x"] },
+    ]);
+    assert_eq!(edges_crossing(&clustering, &graph), vec![edge("b", "c"), edge("d", "x"), edge("a", "x")]);
+}
+
+#[test]
+fn test_undirected() {
+    let graph = crate::graphviz::parse("digraph { y -> a; y -> z; a -> b; c -> b; c -> d; e -> d }");
+    let directed = greedy_cluster(&graph, &ClusterParams::default());
+    let undirected = greedy_cluster(&graph, &ClusterParams { undirected: true, ..ClusterParams::default() });
+    let comparison = compare(&directed, &undirected, &graph);
+    // b is a dependency shared by a and c, which only connects them if direction is ignored
+    assert_eq!(comparison.left.num_clusters, 2);
+    assert_eq!(comparison.right.num_clusters, 1);
+    assert!(comparison.stability < 1.0);
+    assert_eq!(comparison.left, ClusteringMetrics::new(&directed, &graph));
+}
+
+#[test]
+fn test_report_markdown() {
+    let graph = crate::graphviz::parse(r#"digraph { gear -> plate -> "iron|ore"; circuit -> plate; circuit -> cable -> copper }"#);
+    let clustering = Clustering { clusters: vec![cluster_of(&graph, &["plate", "gear"]), cluster_of(&graph, &["cable", "copper"])] };
+    let markdown = report_markdown(&clustering.reports(&graph));
+    let lines: Vec<_> = markdown.lines().collect();
+    assert_eq!(lines, vec![
+        "| cluster | size | num_deps | num_outputs | inputs | outputs |",
+        "|---|---:|---:|---:|---|---|",
+        "| plate | 2 | 1 | 0 | iron\\|ore |  |",
+        "| cable | 2 | 0 | 1 |  | cable |",
+    ]);
+}
+
+#[test]
+fn test_tier_range() {
+    let graph = crate::graphviz::parse(r#"digraph {
+        rocket [tier=4] engine [tier=3] gear [tier=2] plate [tier=1] ore
+        rocket -> engine -> gear -> plate -> ore; engine -> plate
+    }"#);
+    let params = ClusterParams { include: Some(tier_range(Some(2.0), Some(3.0))), ..ClusterParams::default() };
+    let clustering = greedy_cluster(&graph, &params);
+    let id_map = graph.id_map();
+    let assignment = clustering.assignment();
+    let mut clustered: Vec<_> = assignment.keys().map(|&node_idx| &*graph[node_idx].id).collect();
+    clustered.sort();
+    assert_eq!(clustered, ["engine", "gear"]);
+    // both edges to the plate from tier 1 are external dependencies
+    let cluster = &clustering.clusters[assignment[&id_map["engine"]]];
+    assert_eq!(cluster.score.num_deps, 2);
+    // the rocket from tier 4 still consumes the engine
+    let nodes: HashSet<_> = [id_map["engine"], id_map["gear"]].iter().copied().collect();
+    assert_eq!(score(&nodes, &graph).num_outputs, 1);
+
+    let below = tier_range(None, Some(1.0));
+    let matching: Vec<_> = graph.node_indices().filter(|&node_idx| below(&graph[node_idx])).map(|node_idx| &*graph[node_idx].id).collect();
+    assert_eq!(matching, ["plate"]);
+}
+
+#[test]
+fn test_io_item_counts() {
+    let graph = crate::graphviz::parse("digraph { gear -> plate; pipe -> plate; plate -> ore; engine -> gear; engine -> pipe }");
+    let id_map = graph.id_map();
+    let nodes: HashSet<_> = ["gear", "pipe"].iter().map(|id| id_map[*id]).collect();
+    // two edges to plate, but only a single consumed item
+    assert_eq!(io_item_counts(&nodes, &graph), (2, 1));
+    assert_eq!(score(&nodes, &graph).num_deps, 2);
+
+    let report = ClusterReport::new(&Cluster::new(id_map["gear"], nodes, &graph), &graph);
+    assert_eq!((report.items_produced, report.items_consumed), (2, 1));
+
+    // the final product engine doesn't have any consumer, but is produced nonetheless, while gear
+    // and pipe are only consumed inside
+    let nodes: HashSet<_> = ["gear", "pipe", "engine"].iter().map(|id| id_map[*id]).collect();
+    assert_eq!(io_item_counts(&nodes, &graph), (1, 3));
+    // plate is consumed inside by gear, but also outside by pipe
+    let nodes: HashSet<_> = ["gear", "plate"].iter().map(|id| id_map[*id]).collect();
+    assert_eq!(io_item_counts(&nodes, &graph), (2, 2));
+}
+
+#[test]
+fn test_write_clustered_dot_color_lists() {
+    let graph = crate::graphviz::parse(r#"digraph { a -> b [color="red:blue"]; b -> c; a [fillcolor="white:gray"] }"#);
+    let id_map = graph.id_map();
+    let clustering = Clustering { clusters: vec![cluster_of(&graph, &["a"]), cluster_of(&graph, &["b", "c"])] };
+    let mut written = Vec::new();
+    write_clustered_dot(&clustering, &graph, &mut written).unwrap();
+    let written = String::from_utf8(written).unwrap();
+    let edge: Vec<_> = written.lines()
+        .skip_while(|line| !line.contains("\"a\" -> \"b\""))
+        .take_while(|line| line.trim() != "]")
+        .map(str::trim)
+        .collect();
+    assert!(edge.contains(&"color = \"red:blue\""), "{}", written);
+    assert!(edge.contains(&"penwidth = \"2\""), "{}", written);
+
+    let colored = colored_graph(&clustering, &graph);
+    assert_eq!(colored[id_map["a"]].attributes["fillcolor"], "white:gray");
+    assert_eq!(colored[id_map["b"]].attributes["fillcolor"], "2");
+}
+
+#[test]
+fn test_frontier_cache() {
+    let graph = crate::graphviz::parse("digraph { a -> b -> c; a -> c; d -> a }");
+    let id_map = graph.id_map();
+    let params = ClusterParams::default();
+    let mut cluster: HashSet<_> = Some(id_map["a"]).into_iter().collect();
+    let frontier = score_frontier(&cluster, &graph, &params);
+    assert_eq!(frontier.iter().map(|&(node_idx, _)| node_idx).collect::<Vec<_>>(), vec![id_map["b"], id_map["c"], id_map["d"]]);
+
+    let mut cache = FrontierCache::new();
+    assert_eq!(cache.score_frontier(&cluster, &graph, &params), &frontier[..]);
+    assert_eq!(cache.score_frontier(&cluster, &graph, &params), &frontier[..]);
+    assert_eq!(cache.computations(), 1);
+    cluster.insert(id_map["b"]);
+    assert_eq!(cache.score_frontier(&cluster, &graph, &params), &score_frontier(&cluster, &graph, &params)[..]);
+    assert_eq!(cache.computations(), 2);
+}
+
+#[test]
+fn test_write_animation_frames() {
+    let graph = crate::graphviz::parse("digraph { a -> b -> c; a -> c; d -> e }");
+    let dir = std::env::temp_dir().join(format!("factorio-cluster-finder-frames-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let frames = write_animation_frames(&graph, &ClusterParams::default(), &dir).unwrap();
+    let (clustering, log) = greedy_cluster_with_log(&graph, &ClusterParams::default());
+    let written = std::fs::read_dir(&dir).unwrap().count();
+    let last = std::fs::read_to_string(dir.join(format!("frame_{:03}.dot", frames - 1))).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(log.decisions.len(), graph.node_count());
+    assert_eq!(frames, log.decisions.len() + 1);
+    assert_eq!(written, frames);
+    assert_eq!(clustering.clusters.len(), log.decisions.iter().filter(|decision| decision.parent.is_none()).count());
+    let last = crate::graphviz::parse(&last);
+    assert!(last.node_indices().all(|node_idx| last[node_idx].attributes.contains_key("fillcolor")));
+}
+
+#[test]
+fn test_merge_cheap_boundaries() {
+    let graph = crate::graphviz::parse(r#"digraph {
+        a -> b; b -> c; a -> c
+        c -> d
+        d -> e; e -> f; d -> f
+        f -> g; f -> h; e -> g; e -> h
+        g -> h
+    }"#);
+    let clustering = Clustering {
+        clusters: vec![cluster_of(&graph, &["a", "b", "c"]), cluster_of(&graph, &["d", "e", "f"]), cluster_of(&graph, &["g", "h"])],
+    };
+    // a single edge connects the first two clusters, four edges the last two
+    let params = ClusterParams::default();
+    let merged = merge_cheap_boundaries(&clustering, &graph, &params, 2.0);
+    assert_eq!(merged.clusters, vec![cluster_of(&graph, &["a", "b", "c", "d", "e", "f"]), cluster_of(&graph, &["g", "h"])]);
+    assert_eq!(merged.clusters[0].score, score(&merged.clusters[0].nodes, &graph));
+    assert_eq!(merge_cheap_boundaries(&clustering, &graph, &params, 1.0), clustering);
+    let merged = merge_cheap_boundaries(&clustering, &graph, &params, 5.0);
+    assert_eq!(merged.clusters, vec![cluster_of(&graph, &["a", "b", "c", "d", "e", "f", "g", "h"])]);
+
+    // the merged cluster is scored like the rest of the clustering
+    let params = ClusterParams { undirected: true, ..ClusterParams::default() };
+    let merged = merge_cheap_boundaries(&clustering, &graph, &params, 2.0);
+    let nodes = &merged.clusters[0].nodes;
+    assert_eq!(merged.clusters[0].score, score_with_params(nodes, &graph, &params));
+    assert_ne!(merged.clusters[0].score, score(nodes, &graph));
+}
+
+#[test]
+fn test_cluster_k() {
+    let graph = crate::graphviz::parse("digraph { a -> b -> c; a -> c; d -> e; e -> f; f -> g; x -> y }");
+    let params = ClusterParams { max_cluster_size: Some(2), ..ClusterParams::default() };
+    let clusters = cluster_k(&graph, 2, &params);
+    assert_eq!(clusters.len(), 2);
+    assert!(clusters.iter().all(|cluster| !cluster.is_empty()));
+    assert_eq!(clusters.iter().map(HashSet::len).sum::<usize>(), graph.node_count());
+    let id_map = graph.id_map();
+    let cluster_of = |id: &str| clusters.iter().position(|cluster| cluster.contains(&id_map[id])).unwrap();
+    // nodes beyond the maximum size still join the cluster they are connected to
+    assert_eq!(cluster_of("a"), cluster_of("c"));
+    assert_eq!(cluster_of("d"), cluster_of("g"));
+
+    let graph = crate::graphviz::parse("digraph { a -> b; c }");
+    let clusters = cluster_k(&graph, 4, &ClusterParams::default());
+    assert_eq!(clusters.len(), 4);
+    assert!(clusters[..2].iter().all(|cluster| !cluster.is_empty()));
+    assert!(clusters[2..].iter().all(HashSet::is_empty));
+    assert_eq!(clusters.iter().map(HashSet::len).sum::<usize>(), 3);
+
+    // unknown seeds are skipped
+    let params = ClusterParams { seeds: vec!["unknown".to_string(), "c".to_string()], ..ClusterParams::default() };
+    let clusters = cluster_k(&graph, 2, &params);
+    assert_eq!(clusters[0], Some(graph.id_map()["c"]).into_iter().collect());
+}
+
+#[test]
+fn test_decision_tree_string() {
+    let graph = crate::graphviz::parse("digraph { circuit -> cable -> copper; circuit -> iron; gear -> iron }");
+    let params = ClusterParams { seeds: vec!["cable".to_string()], ..ClusterParams::default() };
+    let (_, log) = greedy_cluster_with_log(&graph, &params);
+    let tree = decision_tree_string(&log, &graph);
+    assert_eq!(tree, "\
+cable (score: ClusterScore { num_deps: 1, num_outputs: 0 })
+    copper (score: ClusterScore { num_deps: 0, num_outputs: 1 })
+    circuit (score: ClusterScore { num_deps: 1, num_outputs: 0 })
+        iron (score: ClusterScore { num_deps: 0, num_outputs: 0 })
+            gear (score: ClusterScore { num_deps: 0, num_outputs: 0 })
+");
+}
+
+#[test]
+fn test_minimal_enclosing() {
+    let graph = crate::graphviz::parse("digraph { a -> b -> c; a -> d -> e -> c; b -> f; c -> g; x }");
+    let id_map = graph.id_map();
+    let ids = |nodes: HashSet<NodeIndex>| {
+        let mut ids: Vec<_> = nodes.into_iter().map(|node_idx| graph[node_idx].id.as_str()).collect();
+        ids.sort();
+        ids
+    };
+    assert_eq!(ids(minimal_enclosing(&[id_map["a"], id_map["c"]], &graph)), vec!["a", "b", "c"]);
+    assert_eq!(ids(minimal_enclosing(&[id_map["f"], id_map["g"], id_map["b"]], &graph)), vec!["b", "c", "f", "g"]);
+    assert_eq!(ids(minimal_enclosing(&[id_map["e"], id_map["x"]], &graph)), vec!["e", "x"]);
+    assert!(minimal_enclosing(&[], &graph).is_empty());
+}
+
+#[test]
+fn test_bipartite() {
+    let graph = crate::graphviz::parse(r#"digraph {
+        node [kind=item]
+        smelt [kind=recipe]; craft_gear [kind=recipe]; craft_belt [kind=recipe]; craft_inserter [kind=recipe]
+        smelt -> ore; plate -> smelt
+        craft_gear -> plate; gear -> craft_gear
+        craft_belt -> gear; craft_belt -> plate; belt -> craft_belt
+        craft_inserter -> gear; inserter -> craft_inserter
+    }"#);
+    let id_map = graph.id_map();
+    let params = ClusterParams { model: GraphModel::Bipartite, ..ClusterParams::default() };
+    let nodes = |ids: &[&str]| -> HashSet<_> { ids.iter().map(|id| id_map[*id]).collect() };
+    // ore is consumed, plate and gear are produced and consumed by recipes outside
+    assert_eq!(score_with_params(&nodes(&["smelt", "craft_gear"]), &graph, &params), ClusterScore { num_deps: 1, num_outputs: 2 });
+    // item nodes are shared inputs and outputs instead of members
+    assert_eq!(score_with_params(&nodes(&["smelt", "craft_gear", "gear", "plate"]), &graph, &params), ClusterScore { num_deps: 1, num_outputs: 2 });
+    assert_eq!(score_with_params(&nodes(&["smelt", "craft_gear", "craft_belt"]), &graph, &params), ClusterScore { num_deps: 1, num_outputs: 1 });
+    assert_eq!(score_with_params(&nodes(&["craft_belt"]), &graph, &params), ClusterScore { num_deps: 2, num_outputs: 0 });
+
+    let clustering = greedy_cluster(&graph, &params);
+    assert!(clustering.clusters.iter().flat_map(|cluster| &cluster.nodes).all(|&node_idx| !is_item(&graph, node_idx)));
+    assert_eq!(clustering.clusters.iter().map(|cluster| cluster.nodes.len()).sum::<usize>(), 4);
+}
+
+#[test]
+fn test_dedup_clusterings() {
+    let graph = crate::graphviz::parse("digraph { a -> b -> c -> d -> e -> f -> g -> h }");
+    let clustering = |clusters: &[&[&str]]| Clustering {
+        clusters: clusters.iter()
+            .map(|ids| cluster_of(&graph, ids))
+            .collect(),
+    };
+    let runs = [
+        clustering(&[&["a", "b", "c", "d"], &["e", "f", "g", "h"]]),
+        clustering(&[&["a", "b", "c"], &["d", "e", "f", "g", "h"]]),
+        clustering(&[&["a", "c", "e", "g"], &["b", "d", "f", "h"]]),
+    ];
+    // moving d changes 7 of 28 pairs, i.e., the first two runs have a Rand index of 0.75
+    assert_eq!(dedup_clusterings(&runs, 0.7), vec![0, 2]);
+    assert_eq!(dedup_clusterings(&runs, 0.75), vec![0, 1, 2]);
+    assert_eq!(dedup_clusterings(&runs, 0.0), vec![0]);
+    assert!(dedup_clusterings(&[], 0.8).is_empty());
+}
+
+#[test]
+fn test_build_order() {
+    let graph = crate::graphviz::parse("digraph { circuit -> cable -> copper; circuit -> iron; inserter -> circuit; inserter -> gear -> iron; x -> y -> z -> y }");
+    let id_map = graph.id_map();
+    let nodes = |ids: &[&str]| -> HashSet<_> { ids.iter().map(|id| id_map[*id]).collect() };
+    let order = build_order(&nodes(&["inserter", "circuit", "cable", "gear"]), &graph).unwrap();
+    let ids: Vec<_> = order.iter().map(|&node_idx| graph[node_idx].id.as_str()).collect();
+    assert_eq!(ids, vec!["cable", "circuit", "gear", "inserter"]);
+
+    let err = build_order(&nodes(&["x", "y", "z"]), &graph).unwrap_err();
+    assert!(err.id == "y" || err.id == "z", "{}", err);
+}
+
+#[test]
+fn test_alternative_group() {
+    let graph = crate::graphviz::parse(r#"digraph {
+        plastic -> coal [alternative_group=carbon]
+        plastic -> oil [alternative_group=carbon]
+        plastic -> gas
+        lamp -> coal [alternative_group=a]; lamp -> oil [alternative_group=b]
+    }"#);
+    let id_map = graph.id_map();
+    let nodes = |ids: &[&str]| -> HashSet<_> { ids.iter().map(|id| id_map[*id]).collect() };
+    assert_eq!(score(&nodes(&["plastic"]), &graph).num_deps, 2);
+    // an alternative produced internally satisfies the whole group
+    assert_eq!(score(&nodes(&["plastic", "oil"]), &graph).num_deps, 1);
+    assert_eq!(score(&nodes(&["lamp"]), &graph).num_deps, 2);
+    let undirected = ClusterParams { undirected: true, ..ClusterParams::default() };
+    assert_eq!(score_with_params(&nodes(&["plastic"]), &graph, &undirected).num_deps, 2);
+}
+
+#[test]
+fn test_condense_with_html_labels() {
+    let graph = crate::graphviz::parse(r#"digraph {
+        circuit -> cable -> copper; circuit -> iron; "a<b" -> circuit
+        gear -> iron; x1 -> gear; x2 -> gear; x3 -> gear; x4 -> gear; x5 -> gear
+    }"#);
+    let clustering = Clustering {
+        clusters: vec![
+            cluster_of(&graph, &["circuit", "cable", "copper", "a<b"]),
+            cluster_of(&graph, &["gear", "iron", "x1", "x2", "x3", "x4", "x5"]),
+        ],
+    };
+    let condensed = condense_with_html_labels(&clustering, &graph);
+    let label = &condensed[NodeIndex::new(0)].attributes["label"];
+    assert!(label.is_html() && label.starts_with("<<table") && label.ends_with("</table>>"), "{}", label);
+    assert!(label.contains("<tr><td>inputs: 1, outputs: 0</td></tr><tr><td>circuit</td></tr><tr><td>cable</td></tr><tr><td>a&lt;b</td></tr>"), "{}", label);
+    let label = &condensed[NodeIndex::new(1)].attributes["label"];
+    assert!(label.contains("<tr><td>gear</td></tr><tr><td>iron</td></tr><tr><td>x1</td></tr>"), "{}", label);
+    assert!(label.contains("… 2 more"), "{}", label);
+    assert!(!label.contains("x5"), "{}", label);
+
+    let mut written = Vec::new();
+    condensed.write(&mut written).unwrap();
+    let reparsed = crate::graphviz::parse(&String::from_utf8(written).unwrap());
+    assert_eq!(reparsed[NodeIndex::new(1)].attributes["label"], *label);
+}
+
+#[test]
+fn test_universe() {
+    let graph = crate::graphviz::parse("digraph { circuit -> cable -> copper; circuit -> iron; gear -> iron; belt -> gear }");
+    let id_map = graph.id_map();
+    let mut universe = graph.dependencies_closure(id_map["circuit"]);
+    universe.insert(id_map["circuit"]);
+    let params = ClusterParams { universe: Some(universe.clone()), ..ClusterParams::default() };
+    let clustering = greedy_cluster(&graph, &params);
+    let clustered: HashSet<_> = clustering.clusters.iter().flat_map(|cluster| cluster.nodes.iter().copied()).collect();
+    assert_eq!(clustered, universe);
+}
+
+#[test]
+fn test_feedstock() {
+    let graph = crate::graphviz::parse("digraph { belt -> gear; belt -> plate; gear -> plate; plate -> ore; gear -> ore }");
+    let id_map = graph.id_map();
+    let nodes = |ids: &[&str]| ids.iter().map(|id| id_map[*id]).collect::<HashSet<_>>();
+    assert_eq!(feedstock(&nodes(&["belt", "gear"]), &graph), nodes(&["plate", "ore"]));
+    assert_eq!(feedstock(&nodes(&["belt", "gear", "plate"]), &graph), nodes(&["ore"]));
+    assert!(feedstock(&nodes(&["ore"]), &graph).is_empty());
+
+    let graph = crate::graphviz::parse(r#"digraph {
+        node [kind=item]
+        smelt [kind=recipe]; craft_gear [kind=recipe]; craft_belt [kind=recipe]
+        smelt -> ore; plate -> smelt
+        craft_gear -> plate; gear -> craft_gear
+        craft_belt -> gear; craft_belt -> plate; belt -> craft_belt
+    }"#);
+    let id_map = graph.id_map();
+    let nodes = |ids: &[&str]| ids.iter().map(|id| id_map[*id]).collect::<HashSet<_>>();
+    // gear is produced by craft_gear
+    assert_eq!(feedstock(&nodes(&["craft_gear", "craft_belt"]), &graph), nodes(&["plate"]));
+    assert_eq!(feedstock(&nodes(&["smelt", "craft_gear", "craft_belt"]), &graph), nodes(&["ore"]));
+}
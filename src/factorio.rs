@@ -0,0 +1,378 @@
+//! Factorio specific helpers on top of the clustering, like exports for mods.
+//!
+//! The production calculations use the following attributes of the dependency graph:
+//!
+//! * `amount` on an edge `a -> b`: number of `b` required per crafted `a`, defaulting to 1.
+//! * `time` on a node: crafting time in seconds per crafted item, defaulting to 1.
+//! * `size` on a node: area in tiles covered by one machine crafting the item.
+
+use std::collections::{HashMap, HashSet};
+
+use log::*;
+use petgraph::Direction;
+use petgraph::visit::{DfsPostOrder, EdgeRef};
+use serde_json::{json, Value};
+
+use crate::cluster::{self, Clustering};
+use crate::graphviz::{AttrValue, DotGraph, NodeIndex};
+
+/// Parses the attribute with the given key as number, falling back to `default` if it's missing
+/// or invalid.
+fn attribute_f64(attributes: &HashMap<String, AttrValue>, key: &str, default: f64) -> f64 {
+    match attributes.get(key) {
+        Some(value) => value.parse().unwrap_or_else(|_| {
+            warn!("invalid {} {:?}, using {}", key, value, default);
+            default
+        }),
+        None => default,
+    }
+}
+
+/// Calculates the bill of materials, i.e., the rate in items per second at which each item is
+/// required to produce `target` at `rate` items per second.
+///
+/// Edges closing a cycle are ignored, as a cycle doesn't have a finite bill of materials.
+/// The result contains `target` itself and all of its transitive dependencies.
+pub fn bill_of_materials(graph: &DotGraph, target: NodeIndex, rate: f64) -> HashMap<NodeIndex, f64> {
+    // reverse post order visits each item before all of its dependencies
+    let mut order = Vec::new();
+    let mut dfs = DfsPostOrder::new(&**graph, target);
+    while let Some(node_idx) = dfs.next(&**graph) {
+        order.push(node_idx);
+    }
+    order.reverse();
+    let position: HashMap<_, _> = order.iter().enumerate().map(|(i, &node_idx)| (node_idx, i)).collect();
+
+    let mut rates = HashMap::new();
+    rates.insert(target, rate);
+    for (i, &node_idx) in order.iter().enumerate() {
+        let node_rate = rates[&node_idx];
+        for edge in graph.edges_directed(node_idx, Direction::Outgoing) {
+            let dependency = edge.target();
+            if position[&dependency] <= i {
+                debug!("ignoring cyclic dependency {} -> {}", graph[node_idx].id, graph[dependency].id);
+                continue;
+            }
+            *rates.entry(dependency).or_insert(0.0) += node_rate * edge.weight().amount();
+        }
+    }
+    rates
+}
+
+/// Calculates the number of machines crafting each item required to produce `target` at `rate`
+/// items per second, with machines of the given crafting speed.
+///
+/// Raw resources are mined instead of crafted and thus not part of the result.
+pub fn assembler_counts(graph: &DotGraph, target: NodeIndex, rate: f64, speed: f64) -> HashMap<NodeIndex, f64> {
+    bill_of_materials(graph, target, rate).into_iter()
+        .filter(|&(node_idx, _)| !graph.is_raw_resource(node_idx))
+        .map(|(node_idx, rate)| (node_idx, rate * attribute_f64(&graph[node_idx].attributes, "time", 1.0) / speed))
+        .collect()
+}
+
+/// Returns the number of belts required to transport `flow` items per second on belts moving
+/// `belt_capacity` items per second each, or `None` if `belt_capacity` isn't positive.
+pub fn belts_needed(flow: f64, belt_capacity: f64) -> Option<usize> {
+    if belt_capacity > 0.0 {
+        Some((flow / belt_capacity).ceil() as usize)
+    } else {
+        None
+    }
+}
+
+/// An intermediate on the main bus together with the number of belt lanes it needs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BusLane {
+    /// The item transported on the lanes.
+    pub item: NodeIndex,
+    /// Items per second required across all clusters.
+    pub throughput: f64,
+    /// Number of lanes required to transport the throughput.
+    pub lanes: usize,
+}
+
+/// Recommends the number of main bus lanes for each intermediate shared between clusters, i.e.,
+/// produced in one cluster and consumed in another, to produce each of the `targets` at `rate`
+/// items per second.
+///
+/// Items which aren't part of any cluster, like excluded hubs, are produced outside of all
+/// clusters and thus get bus lanes as well, as long as a cluster consumes them.
+///
+/// The throughput of an item is its total [`bill_of_materials`](fn.bill_of_materials.html)
+/// across all targets, where each lane moves `lane_capacity` items per second.
+/// Items not required by any target are omitted. The result is ordered by node index.
+/// Returns `None` if an item needs lanes but `lane_capacity` isn't positive.
+pub fn bus_lanes(clustering: &Clustering, graph: &DotGraph, targets: &[NodeIndex], rate: f64, lane_capacity: f64) -> Option<Vec<BusLane>> {
+    let mut shared: Vec<_> = cluster::boundary_edges(clustering, graph)
+        // the target of an edge is the dependency, i.e., the item put on the bus
+        .filter(|boundary| boundary.from_cluster.is_some())
+        .map(|boundary| boundary.to_node)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    shared.sort();
+    let mut throughputs = HashMap::new();
+    for &target in targets {
+        for (node_idx, rate) in bill_of_materials(graph, target, rate) {
+            *throughputs.entry(node_idx).or_insert(0.0) += rate;
+        }
+    }
+    shared.into_iter()
+        .filter_map(|item| throughputs.get(&item).map(|&throughput| (item, throughput)))
+        .map(|(item, throughput)| Some(BusLane {
+            item,
+            throughput,
+            lanes: belts_needed(throughput, lane_capacity)?,
+        }))
+        .collect()
+}
+
+/// Estimates the area in tiles covered by the machines of the cluster, given the machine counts
+/// of each item, e.g. from [`assembler_counts`](fn.assembler_counts.html).
+///
+/// Fractional machine counts are rounded up to whole machines. Each machine covers the `size`
+/// attribute of its item in tiles, defaulting to `tiles_per_machine`.
+/// Items of the cluster without a machine count, like raw resources, don't cover any area.
+pub fn cluster_footprint(cluster: &HashSet<NodeIndex>, graph: &DotGraph, counts: &HashMap<NodeIndex, f64>, tiles_per_machine: f64) -> usize {
+    let area: f64 = cluster.iter()
+        .filter_map(|node_idx| counts.get(node_idx).map(|count| (node_idx, count)))
+        .map(|(&node_idx, count)| count.ceil() * attribute_f64(&graph[node_idx].attributes, "size", tiles_per_machine))
+        .sum();
+    area.ceil() as usize
+}
+
+/// Returns the recipe requiring the most machines to produce `target` at `rate` items per
+/// second, together with that number of machines.
+///
+/// # Panics
+///
+/// Panics if `target` is a raw resource, as no recipe is required to produce it.
+pub fn bottleneck(graph: &DotGraph, target: NodeIndex, rate: f64, speed: f64) -> (NodeIndex, f64) {
+    assembler_counts(graph, target, rate, speed).into_iter()
+        .max_by(|(a_idx, a), (b_idx, b)| a.total_cmp(b).then(b_idx.cmp(a_idx)))
+        .expect("target is a raw resource")
+}
+
+/// How [`bom_tree_json`](fn.bom_tree_json.html) represents items required by several recipes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SharedSubtrees {
+    /// Repeats the whole subtree of the item wherever it's required.
+    Duplicate,
+    /// Only expands the first occurrence of the item, later occurrences are references.
+    Reference,
+}
+
+/// Converts the dependency tree required to produce `target` at `rate` items per second into
+/// nested JSON, e.g. for display in a web UI.
+///
+/// Each item is an object `{"item", "amount", "machines", "children"}`, where `amount` is the
+/// rate required by its parent and `machines` is the number of machines of crafting speed 1
+/// producing that rate, which is 0 for raw resources.
+/// Items are referenced as `{"ref", "amount"}` with the id of the item instead of being expanded
+/// if they close a cycle, or if they occur again while `shared` is `Reference`.
+/// Children are ordered by node index.
+pub fn bom_tree_json(graph: &DotGraph, target: NodeIndex, rate: f64, shared: SharedSubtrees) -> String {
+    fn tree(graph: &DotGraph, node_idx: NodeIndex, rate: f64, shared: SharedSubtrees,
+            path: &mut Vec<NodeIndex>, expanded: &mut HashSet<NodeIndex>) -> Value {
+        let item = &graph[node_idx].id;
+        if path.contains(&node_idx) || (shared == SharedSubtrees::Reference && expanded.contains(&node_idx)) {
+            return json!({ "ref": item, "amount": rate });
+        }
+        expanded.insert(node_idx);
+        let machines = if graph.is_raw_resource(node_idx) {
+            0.0
+        } else {
+            rate * attribute_f64(&graph[node_idx].attributes, "time", 1.0)
+        };
+        let mut edges: Vec<_> = graph.edges_directed(node_idx, Direction::Outgoing).collect();
+        edges.sort_by_key(|edge| edge.target());
+        path.push(node_idx);
+        let children: Vec<_> = edges.into_iter()
+            .map(|edge| tree(graph, edge.target(), rate * edge.weight().amount(), shared, path, expanded))
+            .collect();
+        path.pop();
+        json!({ "item": item, "amount": rate, "machines": machines, "children": children })
+    }
+    let value = tree(graph, target, rate, shared, &mut Vec::new(), &mut HashSet::new());
+    serde_json::to_string_pretty(&value).unwrap()
+}
+
+/// Quotes the given string as a Lua string literal.
+fn lua_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\{}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Converts the clustering into a Lua table literal mapping cluster names to their item ids.
+///
+/// Clusters are named `cluster_1`, `cluster_2`, … in the order of the clustering, following
+/// Lua's 1-based indexing.
+///
+/// # Example
+/// ```text
+/// {
+///   ["cluster_1"] = {"iron-gear-wheel", "iron-plate"},
+/// }
+/// ```
+pub fn clusters_to_lua(clustering: &Clustering) -> String {
+    let mut lua = String::from("{\n");
+    for (i, cluster) in clustering.clusters.iter().enumerate() {
+        let items: Vec<_> = cluster.ids.iter().map(|id| lua_string(id)).collect();
+        lua += &format!("  [{}] = {{{}}},\n", lua_string(&format!("cluster_{}", i + 1)), items.join(", "));
+    }
+    lua += "}\n";
+    lua
+}
+
+#[test]
+fn test_clusters_to_lua() {
+    use crate::cluster::{self, ClusterOrder, ClusterParams};
+
+    let graph = crate::graphviz::parse(r#"digraph { "iron-gear" -> "iron-plate"; "odd \"name\"" }"#);
+    let mut clustering = cluster::greedy_cluster(&graph, &ClusterParams::default());
+    clustering.sort_by(ClusterOrder::Size);
+    let lua = clusters_to_lua(&clustering);
+    assert_eq!(lua, "{\n  [\"cluster_1\"] = {\"iron-gear\", \"iron-plate\"},\n  [\"cluster_2\"] = {\"odd \\\"name\\\"\"},\n}\n");
+
+    // structurally a table: balanced braces and brackets outside of strings
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut chars = lua.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if in_string => { chars.next(); }
+            '"' => in_string = !in_string,
+            '{' | '[' if !in_string => depth += 1,
+            '}' | ']' if !in_string => depth -= 1,
+            _ => {}
+        }
+        assert!(depth >= 0);
+    }
+    assert!(!in_string);
+    assert_eq!(depth, 0);
+}
+
+#[test]
+fn test_bottleneck() {
+    // 1 circuit needs 3 copper cables and 1 iron plate, 2 cables need 1 copper plate
+    let graph = crate::graphviz::parse(r#"digraph {
+        circuit [time=0.5]; cable [time=0.25]; "iron-plate" [time=4]; "copper-plate" [time=4]
+        circuit -> cable [amount=3]
+        circuit -> "iron-plate"
+        cable -> "copper-plate" [amount=0.5]
+        "iron-plate" -> "iron-ore"
+        "copper-plate" -> "copper-ore"
+    }"#);
+    let id_map = graph.id_map();
+    let rates = bill_of_materials(&graph, id_map["circuit"], 2.0);
+    assert_eq!(rates[&id_map["cable"]], 6.0);
+    assert_eq!(rates[&id_map["copper-plate"]], 3.0);
+    assert_eq!(rates[&id_map["iron-ore"]], 2.0);
+
+    let counts = assembler_counts(&graph, id_map["circuit"], 2.0, 0.5);
+    assert!(!counts.contains_key(&id_map["iron-ore"]));
+    assert_eq!(counts[&id_map["circuit"]], 2.0);
+    assert_eq!(counts[&id_map["cable"]], 3.0);
+    assert_eq!(counts[&id_map["iron-plate"]], 16.0);
+    assert_eq!(bottleneck(&graph, id_map["circuit"], 2.0, 0.5), (id_map["copper-plate"], 24.0));
+}
+
+#[test]
+fn test_cluster_footprint() {
+    let graph = crate::graphviz::parse(r#"digraph { circuit [size=9]; cable; "refinery" [size=25]; circuit -> cable; ore }"#);
+    let id_map = graph.id_map();
+    let counts: HashMap<_, _> = vec![(id_map["circuit"], 2.0), (id_map["cable"], 2.5), (id_map["refinery"], 1.0)].into_iter().collect();
+    let cluster: HashSet<_> = ["circuit", "cable", "ore"].iter().map(|id| id_map[*id]).collect();
+    // 2 circuit machines of 9 tiles and 3 cable machines of the default 4 tiles
+    assert_eq!(cluster_footprint(&cluster, &graph, &counts, 4.0), 30);
+}
+
+#[test]
+fn test_bom_tree_json() {
+    let graph = crate::graphviz::parse(r#"digraph {
+        circuit [time=0.5]
+        cable [time=0.5]
+        circuit -> cable [amount=3]
+        circuit -> iron
+        cable -> copper
+    }"#);
+    let id_map = graph.id_map();
+    let tree: Value = serde_json::from_str(&bom_tree_json(&graph, id_map["circuit"], 2.0, SharedSubtrees::Duplicate)).unwrap();
+    assert_eq!(tree, json!({
+        "item": "circuit", "amount": 2.0, "machines": 1.0, "children": [
+            { "item": "cable", "amount": 6.0, "machines": 3.0, "children": [
+                { "item": "copper", "amount": 6.0, "machines": 0.0, "children": [] },
+            ] },
+            { "item": "iron", "amount": 2.0, "machines": 0.0, "children": [] },
+        ],
+    }));
+
+    let graph = crate::graphviz::parse("digraph { a -> b -> c; a -> c; c -> a }");
+    let id_map = graph.id_map();
+    let tree: Value = serde_json::from_str(&bom_tree_json(&graph, id_map["a"], 1.0, SharedSubtrees::Duplicate)).unwrap();
+    let cycle = json!({ "ref": "a", "amount": 1.0 });
+    assert_eq!(tree["children"][0]["children"][0]["children"][0], cycle);
+    assert_eq!(tree["children"][1]["children"][0], cycle);
+    let tree: Value = serde_json::from_str(&bom_tree_json(&graph, id_map["a"], 1.0, SharedSubtrees::Reference)).unwrap();
+    assert_eq!(tree["children"][0]["children"][0]["children"][0], cycle);
+    assert_eq!(tree["children"][1], json!({ "ref": "c", "amount": 1.0 }));
+}
+
+#[test]
+fn test_bus_lanes() {
+    let graph = crate::graphviz::parse(r#"digraph {
+        red -> gear
+        red -> plate
+        green -> circuit [amount=2]
+        green -> gear
+        circuit -> plate
+        circuit -> cable [amount=3]
+        gear -> plate [amount=2]
+        cable -> copper
+        lonely -> plate
+    }"#);
+    let id_map = graph.id_map();
+    let clustering = Clustering {
+        clusters: vec![
+            cluster::cluster_of(&graph, &["red"]),
+            cluster::cluster_of(&graph, &["green"]),
+            cluster::cluster_of(&graph, &["circuit", "cable"]),
+            cluster::cluster_of(&graph, &["gear", "plate", "copper"]),
+            cluster::cluster_of(&graph, &["lonely"]),
+        ],
+    };
+    let targets = [id_map["red"], id_map["green"]];
+    let lanes: Vec<_> = bus_lanes(&clustering, &graph, &targets, 4.0, 7.5).unwrap().into_iter()
+        .map(|lane| (graph[lane.item].id.as_str(), lane.throughput, lane.lanes))
+        .collect();
+    // red: gear 4, plate 4 + 8; green: circuit 8, gear 4, plate 8 + 8, cable 24, copper 24
+    assert_eq!(lanes, vec![("gear", 8.0, 2), ("plate", 28.0, 4), ("circuit", 8.0, 2), ("copper", 24.0, 4)]);
+
+    // the excluded hub plate isn't part of any cluster, but still needs to reach them
+    let clustering = Clustering {
+        clusters: vec![
+            cluster::cluster_of(&graph, &["red"]),
+            cluster::cluster_of(&graph, &["green", "circuit", "cable", "copper"]),
+            cluster::cluster_of(&graph, &["gear"]),
+            cluster::cluster_of(&graph, &["lonely"]),
+        ],
+    };
+    let lanes: Vec<_> = bus_lanes(&clustering, &graph, &targets, 4.0, 7.5).unwrap().into_iter()
+        .map(|lane| (graph[lane.item].id.as_str(), lane.throughput, lane.lanes))
+        .collect();
+    assert_eq!(lanes, vec![("gear", 8.0, 2), ("plate", 28.0, 4)]);
+    assert_eq!(bus_lanes(&clustering, &graph, &targets, 4.0, 0.0), None);
+}
@@ -0,0 +1,370 @@
+//! Analyses on the dependency structure of a `DotGraph`.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::hash_map::Entry;
+
+use petgraph::Direction;
+use petgraph::algo::{connected_components, tarjan_scc};
+use petgraph::visit::{EdgeFiltered, EdgeRef};
+
+use crate::graphviz::{DotGraph, NodeIndex};
+
+/// Summary of the structure of a graph, see [`DotGraph::stats`](struct.DotGraph.html#method.stats).
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphStats {
+    /// Number of nodes.
+    pub nodes: usize,
+    /// Number of edges.
+    pub edges: usize,
+    /// Number of connected components, ignoring the direction of edges.
+    pub components: usize,
+    /// Number of cycles, where each strongly connected component with more than one node or a
+    /// self-loop counts as one cycle.
+    pub cycles: usize,
+    /// Number of nodes without dependencies.
+    pub raw_resources: usize,
+    /// Number of nodes without consumers.
+    pub final_products: usize,
+    /// Highest [`scc_depth`](struct.DotGraph.html#method.scc_depth) of any node.
+    pub max_depth: usize,
+    /// Average number of edges per node, counting both endpoints of each edge.
+    pub average_degree: f64,
+}
+
+impl DotGraph {
+    /// Returns a summary of the structure of this graph.
+    pub fn stats(&self) -> GraphStats {
+        let cycles = tarjan_scc(&**self).into_iter()
+            .filter(|scc| scc.len() > 1 || self.find_edge(scc[0], scc[0]).is_some())
+            .count();
+        GraphStats {
+            nodes: self.node_count(),
+            edges: self.edge_count(),
+            components: connected_components(&**self),
+            cycles,
+            raw_resources: self.node_indices().filter(|&node| self.is_raw_resource(node)).count(),
+            final_products: self.node_indices()
+                .filter(|&node| self.neighbors_directed(node, Direction::Incoming).next().is_none())
+                .count(),
+            max_depth: self.scc_depth().values().copied().max().unwrap_or(0),
+            average_degree: match self.node_count() {
+                0 => 0.0,
+                nodes => 2.0 * self.edge_count() as f64 / nodes as f64,
+            },
+        }
+    }
+
+    /// Returns whether the given node is a raw resource, i.e., doesn't have any dependencies.
+    pub fn is_raw_resource(&self, node: NodeIndex) -> bool {
+        self.neighbors_directed(node, Direction::Outgoing).next().is_none()
+    }
+
+    /// Returns all nodes without any edges, i.e., items which are neither produced nor consumed.
+    ///
+    /// These are usually modeling mistakes or orphaned items.
+    pub fn isolated_nodes(&self) -> Vec<NodeIndex> {
+        self.node_indices()
+            .filter(|&node| self.neighbors_undirected(node).next().is_none())
+            .collect()
+    }
+
+    /// Returns all nodes transitively required to produce the given node.
+    ///
+    /// The node itself is only part of the result if it's part of a cycle.
+    pub fn dependencies_closure(&self, node: NodeIndex) -> HashSet<NodeIndex> {
+        let mut closure = HashSet::new();
+        let mut stack: Vec<_> = self.neighbors_directed(node, Direction::Outgoing).collect();
+        while let Some(dependency) = stack.pop() {
+            if closure.insert(dependency) {
+                stack.extend(self.neighbors_directed(dependency, Direction::Outgoing));
+            }
+        }
+        closure
+    }
+
+    /// Returns the leverage of each node, i.e., the number of distinct final products
+    /// transitively depending on it, where final products are nodes without consumers.
+    ///
+    /// High-leverage items are candidates for mass production, e.g. on a main bus.
+    /// A final product doesn't depend on itself and thus doesn't count towards its own leverage.
+    pub fn leverage(&self) -> HashMap<NodeIndex, usize> {
+        self.node_indices()
+            .map(|node| {
+                let mut consumers = HashSet::new();
+                let mut stack: Vec<_> = self.neighbors_directed(node, Direction::Incoming).collect();
+                while let Some(consumer) = stack.pop() {
+                    if consumers.insert(consumer) {
+                        stack.extend(self.neighbors_directed(consumer, Direction::Incoming));
+                    }
+                }
+                let products = consumers.into_iter()
+                    .filter(|&consumer| self.neighbors_directed(consumer, Direction::Incoming).next().is_none())
+                    .count();
+                (node, products)
+            }).collect()
+    }
+
+    /// Returns all nodes which would become unproducible if `node` were deleted, i.e., which lose
+    /// all their production paths to raw resources.
+    ///
+    /// A node is producible if all of its dependencies are, as each dependency is a required
+    /// ingredient.
+    /// Dependencies whose edges share an `alternative_group` attribute are substitutable, such
+    /// that any producible one of them suffices.
+    /// Nodes which aren't producible in the first place, like cycles without raw resources, and
+    /// `node` itself aren't part of the result.
+    pub fn removal_impact(&self, node: NodeIndex) -> HashSet<NodeIndex> {
+        let before = self.producible(None);
+        let after = self.producible(Some(node));
+        before.into_iter()
+            .filter(|&producible| producible != node && !after.contains(&producible))
+            .collect()
+    }
+
+    /// Returns all nodes which can be produced from raw resources without `removed`, see
+    /// [`removal_impact`](#method.removal_impact).
+    fn producible(&self, removed: Option<NodeIndex>) -> HashSet<NodeIndex> {
+        let mut producible = HashSet::new();
+        loop {
+            let before = producible.len();
+            for node in self.node_indices() {
+                if Some(node) != removed && !producible.contains(&node) && self.has_ingredients(node, &producible) {
+                    producible.insert(node);
+                }
+            }
+            if producible.len() == before {
+                return producible;
+            }
+        }
+    }
+
+    /// Returns whether all dependencies of the node are part of `available`, where one available
+    /// dependency of each `alternative_group` suffices.
+    fn has_ingredients(&self, node: NodeIndex, available: &HashSet<NodeIndex>) -> bool {
+        let mut alternatives: HashMap<&str, bool> = HashMap::new();
+        for edge in self.edges_directed(node, Direction::Outgoing) {
+            let is_available = available.contains(&edge.target());
+            match edge.weight().attributes.get("alternative_group") {
+                Some(group) => *alternatives.entry(group).or_insert(false) |= is_available,
+                None if !is_available => return false,
+                None => (),
+            }
+        }
+        alternatives.values().all(|&is_available| is_available)
+    }
+
+    /// Visits all nodes reachable from `start` in breadth-first order, following edges in the
+    /// given direction.
+    ///
+    /// The visitor is called once per node with the node and its distance from `start`, starting
+    /// with `start` itself at distance 0.
+    /// If it returns `false`, the neighbors of that node aren't descended into, although they may
+    /// still be reached via other nodes.
+    pub fn visit_bfs(&self, start: NodeIndex, direction: Direction, visitor: &mut dyn FnMut(NodeIndex, usize) -> bool) {
+        let mut visited = HashSet::new();
+        visited.insert(start);
+        let mut queue = VecDeque::new();
+        queue.push_back((start, 0));
+        while let Some((node, distance)) = queue.pop_front() {
+            if !visitor(node, distance) {
+                continue;
+            }
+            for neighbor in self.neighbors_directed(node, direction) {
+                if visited.insert(neighbor) {
+                    queue.push_back((neighbor, distance + 1));
+                }
+            }
+        }
+    }
+
+    /// Returns the diameter of this graph, i.e., the longest shortest path between any two nodes,
+    /// ignoring the direction of edges.
+    ///
+    /// Returns `None` if the graph is empty or disconnected, as the distance between nodes of
+    /// different components is infinite.
+    pub fn diameter(&self) -> Option<usize> {
+        self.eccentricities().map(|eccentricities| eccentricities.into_iter().max().unwrap())
+    }
+
+    /// Returns the radius of this graph, i.e., the lowest eccentricity of any node, ignoring the
+    /// direction of edges.
+    ///
+    /// Like [`diameter`](#method.diameter), returns `None` if the graph is empty or disconnected.
+    pub fn radius(&self) -> Option<usize> {
+        self.eccentricities().map(|eccentricities| eccentricities.into_iter().min().unwrap())
+    }
+
+    /// Returns the eccentricity of each node, i.e., its longest shortest path to any other node
+    /// in the undirected projection, or `None` if the graph is empty or disconnected.
+    fn eccentricities(&self) -> Option<Vec<usize>> {
+        if self.node_count() == 0 {
+            return None;
+        }
+        self.node_indices().map(|start| {
+            let mut distances = HashMap::new();
+            distances.insert(start, 0);
+            let mut queue = VecDeque::new();
+            queue.push_back(start);
+            let mut eccentricity = 0;
+            while let Some(node) = queue.pop_front() {
+                let distance = distances[&node];
+                eccentricity = distance;
+                for neighbor in self.neighbors_undirected(node) {
+                    if let Entry::Vacant(entry) = distances.entry(neighbor) {
+                        entry.insert(distance + 1);
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+            if distances.len() == self.node_count() { Some(eccentricity) } else { None }
+        }).collect()
+    }
+
+    /// Returns the depth of each node, i.e., the length of the longest dependency chain from any
+    /// raw resource to the node.
+    ///
+    /// Each strongly connected component counts as a single level, i.e., all nodes of a cycle
+    /// share the same depth, which keeps the depth meaningful for cyclic graphs.
+    /// Raw resources and cycles without outside dependencies have depth 0.
+    /// Edges with `constraint=false` don't affect the depth, like they don't affect the ranking
+    /// in graphviz, see [`Edge::is_constraint`](struct.Edge.html#method.is_constraint).
+    pub fn scc_depth(&self) -> HashMap<NodeIndex, usize> {
+        let constraints = EdgeFiltered::from_fn(&**self, |edge| edge.weight().is_constraint());
+        let mut depths = HashMap::new();
+        // sccs are returned in reverse topological order, i.e., dependencies first
+        for scc in tarjan_scc(&constraints) {
+            let depth = scc.iter()
+                .flat_map(|&node| self.edges_directed(node, Direction::Outgoing))
+                .filter(|edge| edge.weight().is_constraint())
+                .filter_map(|edge| depths.get(&edge.target()))
+                .map(|depth| depth + 1)
+                .max()
+                .unwrap_or(0);
+            depths.extend(scc.into_iter().map(|node| (node, depth)));
+        }
+        depths
+    }
+}
+
+#[test]
+fn test_dependencies_closure() {
+    let graph = crate::graphviz::parse("digraph { a -> b -> c; b -> d; e -> a; f -> f }");
+    let id_map = graph.id_map();
+    let closure: HashSet<_> = ["b", "c", "d"].iter().map(|id| id_map[*id]).collect();
+    assert_eq!(graph.dependencies_closure(id_map["a"]), closure);
+    assert!(graph.dependencies_closure(id_map["f"]).contains(&id_map["f"]));
+    assert!(graph.is_raw_resource(id_map["c"]));
+    assert!(!graph.is_raw_resource(id_map["a"]));
+}
+
+#[test]
+fn test_removal_impact() {
+    let graph = crate::graphviz::parse(r#"digraph {
+        circuit -> cable -> copper
+        circuit -> iron
+        inserter -> circuit
+        gear -> iron
+        plastic -> gas [alternative_group=feedstock]; plastic -> coal [alternative_group=feedstock]
+        plastic -> water
+        bar -> plastic
+        loop -> loop2 -> loop
+    }"#);
+    let id_map = graph.id_map();
+    let ids = |ids: &[&str]| ids.iter().map(|id| id_map[*id]).collect::<HashSet<_>>();
+    assert_eq!(graph.removal_impact(id_map["cable"]), ids(&["circuit", "inserter"]));
+    // circuit requires both cable and iron
+    assert_eq!(graph.removal_impact(id_map["copper"]), ids(&["cable", "circuit", "inserter"]));
+    assert_eq!(graph.removal_impact(id_map["iron"]), ids(&["gear", "circuit", "inserter"]));
+    // plastic has an alternate recipe from coal, but always requires water
+    assert!(graph.removal_impact(id_map["gas"]).is_empty());
+    assert_eq!(graph.removal_impact(id_map["water"]), ids(&["plastic", "bar"]));
+    assert!(graph.removal_impact(id_map["loop"]).is_empty());
+}
+
+#[test]
+fn test_isolated_nodes() {
+    let graph = crate::graphviz::parse("digraph { a -> b; c; d -> d; e -> a }");
+    assert_eq!(graph.isolated_nodes(), vec![graph.id_map()["c"]]);
+}
+
+#[test]
+fn test_visit_bfs() {
+    let graph = crate::graphviz::parse("digraph { a -> b -> c -> d; a -> e -> d; b -> f }");
+    let id_map = graph.id_map();
+    let mut within_two = 0;
+    graph.visit_bfs(id_map["a"], Direction::Outgoing, &mut |_, distance| {
+        if distance <= 2 {
+            within_two += 1;
+        }
+        distance < 2
+    });
+    // a, b, e, c, f, d
+    assert_eq!(within_two, 6);
+
+    let mut distances = HashMap::new();
+    graph.visit_bfs(id_map["d"], Direction::Incoming, &mut |node, distance| {
+        distances.insert(node, distance);
+        true
+    });
+    assert_eq!(distances[&id_map["d"]], 0);
+    assert_eq!(distances[&id_map["a"]], 2);
+    assert!(!distances.contains_key(&id_map["f"]));
+}
+
+#[test]
+fn test_scc_depth() {
+    let graph = crate::graphviz::parse("digraph { ore; plate -> ore; a -> plate; a -> b; b -> c; c -> a; d -> a; e -> d }");
+    let id_map = graph.id_map();
+    let depths = graph.scc_depth();
+    assert_eq!(depths[&id_map["ore"]], 0);
+    assert_eq!(depths[&id_map["plate"]], 1);
+    assert_eq!(depths[&id_map["a"]], 2);
+    assert_eq!(depths[&id_map["b"]], 2);
+    assert_eq!(depths[&id_map["c"]], 2);
+    assert_eq!(depths[&id_map["d"]], 3);
+    assert_eq!(depths[&id_map["e"]], 4);
+}
+
+#[test]
+fn test_scc_depth_constraint() {
+    let dot = "digraph { ore; plate -> ore; gear -> plate; %s }";
+    let graph = crate::graphviz::parse(&dot.replace("%s", ""));
+    let depths = graph.scc_depth();
+    for edge in &["plate -> gear [constraint=false]", "gear -> ore [constraint=no]"] {
+        let constrained = crate::graphviz::parse(&dot.replace("%s", edge));
+        assert_eq!(constrained.scc_depth(), depths);
+    }
+    let constrained = crate::graphviz::parse(&dot.replace("%s", "gear -> ore [constraint=true]"));
+    assert_eq!(constrained.scc_depth(), depths);
+    let constrained = crate::graphviz::parse(&dot.replace("%s", "ore -> gear"));
+    assert_ne!(constrained.scc_depth(), depths);
+}
+
+#[test]
+fn test_leverage() {
+    let graph = crate::graphviz::parse(r#"digraph {
+        gear -> plate; circuit -> plate; plate -> ore
+        belt -> gear; inserter -> gear; inserter -> circuit; lamp -> circuit
+        a -> b -> a
+    }"#);
+    let id_map = graph.id_map();
+    let leverage = graph.leverage();
+    assert_eq!(leverage[&id_map["plate"]], 3);
+    assert_eq!(leverage[&id_map["ore"]], 3);
+    assert_eq!(leverage[&id_map["gear"]], 2);
+    assert_eq!(leverage[&id_map["circuit"]], 2);
+    assert_eq!(leverage[&id_map["belt"]], 0);
+    assert_eq!(leverage[&id_map["a"]], 0);
+}
+
+#[test]
+fn test_diameter() {
+    let graph = crate::graphviz::parse("digraph { a -> b; c -> b; c -> d; d -> e }");
+    assert_eq!(graph.diameter(), Some(4));
+    assert_eq!(graph.radius(), Some(2));
+    let graph = crate::graphviz::parse("digraph { a -> b; c }");
+    assert_eq!(graph.diameter(), None);
+    assert_eq!(graph.radius(), None);
+    assert_eq!(crate::graphviz::parse("digraph { }").diameter(), None);
+    assert_eq!(crate::graphviz::parse("digraph { a }").diameter(), Some(0));
+}
@@ -1,16 +1,58 @@
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
 
 use log::*;
 
 use crate::graphviz::{
+    AttrValue,
     Node,
     Edge,
     NodeIndex,
     DotGraph,
     GraphType,
-    Graph
+    Graph,
+    Subgraph,
 };
 
+/// Error returned by [`DotGraphBuilder::try_build`](struct.DotGraphBuilder.html#method.try_build)
+/// if the builder's parts don't fit together.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum BuildError {
+    /// An edge references a node index which doesn't exist in the graph, e.g. from a stale
+    /// `id_map`.
+    InvalidEndpoint {
+        /// The nonexistent node index.
+        node: NodeIndex,
+        /// Number of nodes of the graph at the time the edge was added.
+        node_count: usize,
+    },
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::InvalidEndpoint { node, node_count } => {
+                write!(f, "edge endpoint {} is out of bounds for {} nodes", node.index(), node_count)
+            }
+        }
+    }
+}
+
+impl Error for BuildError {}
+
+/// Returns an error if any endpoint of the edges doesn't exist in a graph with `node_count` nodes.
+fn check_endpoints(edges: &[(Edge, NodeIndex, NodeIndex)], node_count: usize) -> Result<(), BuildError> {
+    for &(_, source, target) in edges {
+        for &node in &[source, target] {
+            if node.index() >= node_count {
+                return Err(BuildError::InvalidEndpoint { node, node_count });
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Builder to easily create a [`DotGraph`].
 ///
 /// [`DotGraph`]: struct.DotGraph.html
@@ -18,14 +60,15 @@ pub struct DotGraphBuilder {
     strict: Option<bool>,
     _type: GraphType,
     id: Option<Option<String>>,
-    graph_attributes: Option<HashMap<String, String>>,
-    node_attributes: Option<HashMap<String, String>>,
-    edge_attributes: Option<HashMap<String, String>>,
+    graph_attributes: Option<HashMap<String, AttrValue>>,
+    node_attributes: Option<HashMap<String, AttrValue>>,
+    edge_attributes: Option<HashMap<String, AttrValue>>,
     nodes: Option<Vec<Node>>,
     edges: Option<Vec<(Edge, NodeIndex, NodeIndex)>>,
     // we can't use a generic type, because we can't get a named default type
     edges_fn: Option<Box<dyn FnOnce(&DotGraph) -> Vec<(Edge, NodeIndex, NodeIndex)>>>,
     graph: Option<Graph>,
+    subgraphs: Option<Vec<Subgraph>>,
 }
 
 impl DotGraphBuilder {
@@ -42,6 +85,7 @@ impl DotGraphBuilder {
             edges: None,
             edges_fn: None,
             graph: None,
+            subgraphs: None,
         }
     }
     /// Sets or unsets this graph's `strict` attribute as defined by the
@@ -56,17 +100,17 @@ impl DotGraphBuilder {
         self
     }
     /// Sets this graph's global `graph` attributes.
-    pub fn graph_attributes(mut self, attrs: HashMap<String, String>) -> DotGraphBuilder {
+    pub fn graph_attributes(mut self, attrs: HashMap<String, AttrValue>) -> DotGraphBuilder {
         self.graph_attributes = Some(attrs);
         self
     }
     /// Sets this graph's global `node` attributes.
-    pub fn node_attributes(mut self, attrs: HashMap<String, String>) -> DotGraphBuilder {
+    pub fn node_attributes(mut self, attrs: HashMap<String, AttrValue>) -> DotGraphBuilder {
         self.node_attributes = Some(attrs);
         self
     }
     /// Sets this graph's global `edge` attributes.
-    pub fn edge_attributes(mut self, attrs: HashMap<String, String>) -> DotGraphBuilder {
+    pub fn edge_attributes(mut self, attrs: HashMap<String, AttrValue>) -> DotGraphBuilder {
         self.edge_attributes = Some(attrs);
         self
     }
@@ -75,6 +119,11 @@ impl DotGraphBuilder {
         self.graph = Some(graph);
         self
     }
+    /// Sets the subgraphs of the graph, whose node indices refer to the given or default graph.
+    pub fn subgraphs(mut self, subgraphs: Vec<Subgraph>) -> DotGraphBuilder {
+        self.subgraphs = Some(subgraphs);
+        self
+    }
     /// Sets the list of nodes, which will be added to the given or default graph.
     pub fn nodes(mut self, nodes: Vec<Node>) -> DotGraphBuilder {
         self.nodes = Some(nodes);
@@ -96,7 +145,18 @@ impl DotGraphBuilder {
     }
 
     /// Builds and returns the graph.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an edge references a node which doesn't exist, see
+    /// [`try_build`](#method.try_build).
     pub fn build(self) -> DotGraph {
+        self.try_build().unwrap()
+    }
+
+    /// Builds and returns the graph, returning an error if the endpoints of the given edges or
+    /// of the edges returned by the edge-function don't exist in the graph.
+    pub fn try_build(self) -> Result<DotGraph, BuildError> {
         debug!("Building graph from DotGraphBuilder");
         let mut graph = self.graph.unwrap_or_default();
         if let Some(nodes) = self.nodes {
@@ -106,6 +166,7 @@ impl DotGraphBuilder {
         }
 
         if let Some(edges) = self.edges {
+            check_endpoints(&edges, graph.node_count())?;
             for (edge, source, target) in edges {
                 graph.add_edge(source, target, edge);
             }
@@ -120,14 +181,35 @@ impl DotGraphBuilder {
             self.edge_attributes.unwrap_or(HashMap::new()),
             graph,
         );
+        dot_graph.subgraphs = self.subgraphs.unwrap_or_default();
 
         debug!("applying edge function");
         if let Some(edges_fn) = self.edges_fn {
             let edges = edges_fn(&dot_graph);
+            check_endpoints(&edges, dot_graph.node_count())?;
             for (edge, source, target) in edges {
                 dot_graph.add_edge(source, target, edge);
             }
         }
-        dot_graph
+        Ok(dot_graph)
     }
 }
+
+#[test]
+fn test_try_build_invalid_endpoint() {
+    let nodes = vec![Node { id: "a".to_string(), attributes: HashMap::new() }];
+    let edge = Edge { attributes: HashMap::new() };
+    let err = DotGraphBuilder::new(GraphType::Digraph)
+        .nodes(nodes.clone())
+        .edges_fn(move |graph| vec![(edge, graph.id_map()["a"], NodeIndex::new(5))])
+        .try_build()
+        .unwrap_err();
+    assert_eq!(err, BuildError::InvalidEndpoint { node: NodeIndex::new(5), node_count: 1 });
+
+    let graph = DotGraphBuilder::new(GraphType::Digraph)
+        .nodes(nodes)
+        .edges_fn(|graph| vec![(Edge { attributes: HashMap::new() }, graph.id_map()["a"], graph.id_map()["a"])])
+        .try_build()
+        .unwrap();
+    assert_eq!(graph.edge_count(), 1);
+}
@@ -0,0 +1,93 @@
+//! Handling of graphviz color lists like `red:blue` or `red;0.3:blue`.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::graphviz::AttrValue;
+
+/// A single color of a [`ColorList`](struct.ColorList.html) with an optional fraction of the
+/// area it covers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeightedColor {
+    /// Color name or value, e.g. `red` or `#ff0000`.
+    pub color: String,
+    /// Fraction of the area or length covered by this color, if given.
+    pub fraction: Option<f64>,
+}
+
+/// Colon-separated list of colors, which graphviz renders as parallel edges or gradients.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorList {
+    /// Colors in the order they are written.
+    pub colors: Vec<WeightedColor>,
+}
+
+impl ColorList {
+    /// Parses the value of a color attribute.
+    ///
+    /// Fractions which aren't numbers are kept as part of the color.
+    pub fn parse(s: &str) -> ColorList {
+        let colors = s.split(':')
+            .map(|part| match part.rsplit_once(';') {
+                Some((color, fraction)) if fraction.parse::<f64>().is_ok() => WeightedColor {
+                    color: color.to_string(),
+                    fraction: fraction.parse().ok(),
+                },
+                _ => WeightedColor { color: part.to_string(), fraction: None },
+            }).collect();
+        ColorList { colors }
+    }
+
+    /// Returns whether this list consists of more than one color.
+    pub fn is_multi(&self) -> bool {
+        self.colors.len() > 1
+    }
+}
+
+impl fmt::Display for ColorList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, color) in self.colors.iter().enumerate() {
+            if i > 0 {
+                write!(f, ":")?;
+            }
+            write!(f, "{}", color.color)?;
+            if let Some(fraction) = color.fraction {
+                write!(f, ";{}", fraction)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sets the color attribute `key` to a single color, unless it already holds a list of
+/// multiple colors, which is preserved.
+///
+/// Returns whether the attribute was set.
+pub fn set_color(attributes: &mut HashMap<String, AttrValue>, key: &str, color: &str) -> bool {
+    if attributes.get(key).is_some_and(|colors| ColorList::parse(colors).is_multi()) {
+        return false;
+    }
+    attributes.insert(key.to_string(), color.into());
+    true
+}
+
+#[test]
+fn test_color_list() {
+    let colors = ColorList::parse("red;0.25:#00ff00:blue");
+    assert_eq!(colors.colors, vec![
+        WeightedColor { color: "red".to_string(), fraction: Some(0.25) },
+        WeightedColor { color: "#00ff00".to_string(), fraction: None },
+        WeightedColor { color: "blue".to_string(), fraction: None },
+    ]);
+    assert!(colors.is_multi());
+    assert_eq!(colors.to_string(), "red;0.25:#00ff00:blue");
+    assert!(!ColorList::parse("/set312/1").is_multi());
+
+    let mut attributes = HashMap::new();
+    assert!(set_color(&mut attributes, "color", "red"));
+    assert!(set_color(&mut attributes, "color", "blue"));
+    assert_eq!(attributes["color"], "blue");
+    attributes.insert("color".to_string(), "red:blue".into());
+    assert!(!set_color(&mut attributes, "color", "green"));
+    assert_eq!(attributes["color"], "red:blue");
+}
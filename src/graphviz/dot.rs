@@ -4,7 +4,7 @@ use std::collections::{HashMap, HashSet};
 
 use log::*;
 
-use crate::graphviz::{Graph, GraphType, Node, Edge, DotGraph, DotGraphBuilder};
+use crate::graphviz::{AttrValue, Graph, GraphType, Node, NodeIndex, Edge, DotGraph, DotGraphBuilder, Subgraph};
 
 /// Immediate representation of the type of a global attribute
 #[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
@@ -18,16 +18,26 @@ pub enum AttributeType {
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct GlobalAttribute {
     _type: AttributeType,
-    attributes: HashMap<String, String>,
+    attributes: HashMap<String, AttrValue>,
 }
 
 impl GlobalAttribute {
-    pub fn new(_type: AttributeType, attributes: HashMap<String, String>) -> GlobalAttribute {
+    pub fn new(_type: AttributeType, attributes: HashMap<String, AttrValue>) -> GlobalAttribute {
         GlobalAttribute {
             _type,
             attributes,
         }
     }
+
+    /// Returns whether these attributes apply to the graph, all nodes or all edges.
+    pub fn attribute_type(&self) -> AttributeType {
+        self._type
+    }
+
+    /// Returns the attributes set by this statement.
+    pub fn attributes(&self) -> &HashMap<String, AttrValue> {
+        &self.attributes
+    }
 }
 
 /// A dot language `stmt`
@@ -36,13 +46,53 @@ pub enum Statement {
     Node(Node),
     Edge(EdgeInternal),
     GlobalAttribute(GlobalAttribute),
+    Subgraph(SubgraphInternal),
+}
+
+/// Immediate representation of a `subgraph` block
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SubgraphInternal {
+    id: Option<String>,
+    statements: Vec<Statement>,
+}
+
+impl SubgraphInternal {
+    /// Returns the id of this subgraph, if it has one.
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Returns the statements within this subgraph.
+    pub fn statements(&self) -> &[Statement] {
+        &self.statements
+    }
 }
 
 /// Immediate representation of an Edge with attributes
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct EdgeInternal {
-    attributes: HashMap<String, String>,
+    attributes: HashMap<String, AttrValue>,
     nodes: Vec<String>,
+    ports: Vec<Option<String>>,
+}
+
+impl EdgeInternal {
+    /// Returns the ids of all nodes of this edge chain in order, e.g. `a`, `b` and `c` for
+    /// `a -> b -> c`.
+    pub fn nodes(&self) -> &[String] {
+        &self.nodes
+    }
+
+    /// Returns the port of each node of this edge chain, e.g. `None`, `Some("in:w")` and `None`
+    /// for `a -> b:in:w -> c`.
+    pub fn ports(&self) -> &[Option<String>] {
+        &self.ports
+    }
+
+    /// Returns the attributes of all edges of this edge chain.
+    pub fn attributes(&self) -> &HashMap<String, AttrValue> {
+        &self.attributes
+    }
 }
 
 /// Immediate representation of a DotGraph
@@ -56,35 +106,321 @@ pub struct GraphInternal {
 
 include!(concat!(env!("OUT_DIR"), "/dot.rs"));
 
-/// Parses a dot language graph without subgraphs and ports into a DotGraph
+/// Parses a dot language graph and returns its statements without assembling a DotGraph.
+///
+/// This is cheaper than `parse` for tooling only scanning a file, e.g. to extract all node ids.
+pub fn statements(s: &str) -> Result<impl Iterator<Item = Statement>, ParseError> {
+    debug!("parsing str to statements");
+    Ok(graph(s)?.statements.into_iter())
+}
+
+/// Parses a dot language graph into a DotGraph
+///
+/// Ports of edge endpoints like `a:out -> b:in:w` are stored in the `tailport` and `headport`
+/// attributes of the edge, overriding explicitly given ones.
+/// Nodes and edges of subgraphs are flattened into the graph, while named subgraphs are
+/// recorded in [`DotGraph::subgraphs`](struct.DotGraph.html#structfield.subgraphs).
+///
+/// # Panics
+///
+/// Panics if the graph isn't valid dot. Use [`try_parse`](fn.try_parse.html) to handle errors.
 pub fn parse(s: &str) -> DotGraph {
-    debug!("parsing str to DotGraph");
-    let mut graph_internal: GraphInternal = graph(s).unwrap();
-    let mut graph_attributes = HashMap::new();
-    let mut node_attributes = HashMap::new();
-    let mut edge_attributes = HashMap::new();
-    let mut nodes = Vec::new();
-    let mut edges = Vec::new();
-    debug!("Converting statements into values");
-    for stmt in graph_internal.statements.drain(..) {
-        match stmt {
-            Statement::GlobalAttribute(mut attr) => match attr._type {
-                AttributeType::Graph => graph_attributes.extend(attr.attributes.drain()),
-                AttributeType::Node => node_attributes.extend(attr.attributes.drain()),
-                AttributeType::Edge => edge_attributes.extend(attr.attributes.drain()),
-            },
-            Statement::Node(node) => nodes.push(node),
-            Statement::Edge(edge) => edges.push(edge),
+    try_parse(s).unwrap()
+}
+
+/// Parses a dot language graph into a DotGraph, returning an error
+/// if the graph isn't valid dot.
+pub fn try_parse(s: &str) -> Result<DotGraph, ParseError> {
+    parse_with_options(s, &ParseOptions::default())
+}
+
+/// Parses a dot language graph like [`try_parse`](fn.try_parse.html), but skips statements with
+/// syntax errors instead of stopping at the first one, e.g. to report all errors of a file.
+///
+/// Each erroneous statement is removed up to the surrounding statement boundaries, i.e., a `;`,
+/// a line break or a brace of the enclosing block, and parsing is retried, until the rest of the
+/// file parses. The errors refer to the original positions within `s`.
+/// Returns `None` as graph if an error can't be recovered from, e.g. within the header of the
+/// graph or an unterminated string.
+pub fn parse_lenient(s: &str) -> (Option<DotGraph>, Vec<ParseError>) {
+    let mut text = s.to_string();
+    let mut errors = Vec::new();
+    loop {
+        match try_parse(&text) {
+            Ok(graph) => return (Some(graph), errors),
+            Err(e) => {
+                let span = statement_span(&text, e.offset);
+                errors.push(e);
+                match span {
+                    // blank the statement, keeping line breaks such that positions stay the same
+                    Some((start, end)) if text[start..end].trim() != "" => {
+                        let blanked: String = text[start..end].chars()
+                            .map(|c| if c == '\n' { "\n".to_string() } else { " ".repeat(c.len_utf8()) })
+                            .collect();
+                        text.replace_range(start..end, &blanked);
+                    }
+                    _ => return (None, errors),
+                }
+            }
         }
     }
+}
+
+/// Returns the byte range of the statement containing `offset`, delimited by the surrounding
+/// statement boundaries within the same block, or `None` if `offset` isn't within any block.
+fn statement_span(s: &str, offset: usize) -> Option<(usize, usize)> {
+    // (index, character, brace depth before the character) of all characters outside of
+    // strings and attribute lists
+    let mut chars = Vec::new();
+    let (mut braces, mut brackets, mut quoted, mut escaped) = (0usize, 0usize, false, false);
+    for (i, c) in s.char_indices() {
+        if !quoted && brackets == 0 && c != '[' {
+            chars.push((i, c, braces));
+        }
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            _ if quoted => (),
+            '[' => brackets += 1,
+            ']' => brackets = brackets.saturating_sub(1),
+            '{' if brackets == 0 => braces += 1,
+            '}' if brackets == 0 => braces = braces.saturating_sub(1),
+            _ => (),
+        }
+    }
+    let position = chars.iter().position(|&(i, _, _)| i >= offset)?;
+    let depth = chars[position].2;
+    if depth == 0 {
+        return None;
+    }
+    let start = chars[..position].iter()
+        .rev()
+        .find(|&&(_, c, d)| match c {
+            ';' | '\n' => d == depth,
+            '{' => d + 1 == depth,
+            '}' => d == depth + 1,
+            _ => false,
+        }).map_or(0, |&(i, _, _)| i + 1);
+    let end = chars[position..].iter()
+        .find(|&&(_, c, d)| matches!(c, ';' | '\n' | '}') && d == depth)
+        .map_or(s.len(), |&(i, _, _)| i);
+    Some((start, end))
+}
+
+/// Defines which edges an edge chain like `a -> b -> c` expands to.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy, Default)]
+pub enum EdgeChainMode {
+    /// Edges between consecutive nodes, i.e., `a -> b` and `b -> c` as defined by the dot
+    /// language.
+    #[default]
+    Sequential,
+    /// Edges from the first node to all others, i.e., `a -> b` and `a -> c`.
+    StarFromHead,
+    /// Edges from all other nodes to the last one, i.e., `a -> c` and `b -> c`.
+    StarToTail,
+}
+
+impl EdgeChainMode {
+    /// Returns the index pairs of the edges a chain of `len` nodes expands to.
+    fn pairs(self, len: usize) -> Vec<(usize, usize)> {
+        if len < 2 {
+            return Vec::new();
+        }
+        match self {
+            EdgeChainMode::Sequential => (1..len).map(|i| (i - 1, i)).collect(),
+            EdgeChainMode::StarFromHead => (1..len).map(|i| (0, i)).collect(),
+            EdgeChainMode::StarToTail => (0..len - 1).map(|i| (i, len - 1)).collect(),
+        }
+    }
+}
+
+/// Defines which attributes a node declared multiple times ends up with.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy, Default)]
+pub enum DuplicateNodePolicy {
+    /// Only the attributes of the first declaration are kept.
+    #[default]
+    FirstWins,
+    /// Only the attributes of the last declaration are kept.
+    LastWins,
+    /// The attributes of all declarations are merged, where later declarations override the
+    /// values of earlier ones like graphviz does.
+    MergeAttributes,
+}
+
+/// Options changing how a dot language graph is converted into a DotGraph.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Default)]
+pub struct ParseOptions {
+    /// Expansion of edge chains into single edges.
+    pub edge_chain: EdgeChainMode,
+    /// Lowercase attribute keys, which are matched case-insensitively and normalized to
+    /// lowercase, e.g. `["label"]` turns `Label="x"` into `label="x"`.
+    ///
+    /// If an attribute list contains the same key in different casings, the lowercase one wins.
+    /// All other keys are left untouched.
+    pub lowercase_keys: Vec<String>,
+    /// Handling of the attributes of nodes declared multiple times.
+    pub duplicate_nodes: DuplicateNodePolicy,
+    /// Attribute keys whose values may be simple fractions, which are evaluated into decimals,
+    /// e.g. `["amount"]` turns `amount="1/3"` into `amount="0.3333333333333333"`.
+    ///
+    /// Only values of the form `a/b` with decimal numbers `a` and `b != 0` are evaluated, all other
+    /// values are left untouched.
+    pub fraction_keys: Vec<String>,
+}
+
+impl ParseOptions {
+    /// Renames all attributes whose key is one of `lowercase_keys` in a different casing and
+    /// evaluates the fractions of `fraction_keys`.
+    fn normalize_attributes(&self, attributes: &mut HashMap<String, AttrValue>) {
+        let keys: Vec<_> = attributes.keys()
+            .filter(|key| self.lowercase_keys.iter().any(|lower| key.eq_ignore_ascii_case(lower) && *key != lower))
+            .cloned()
+            .collect();
+        for key in keys {
+            let value = attributes.remove(&key).unwrap();
+            attributes.entry(key.to_ascii_lowercase()).or_insert(value);
+        }
+        for key in &self.fraction_keys {
+            if let Some(value) = attributes.get_mut(key) {
+                if let Some(fraction) = evaluate_fraction(value) {
+                    *value = fraction.to_string().into();
+                }
+            }
+        }
+    }
+}
+
+/// Evaluates a fraction like `1/3`, returning `None` if the value isn't a fraction of two
+/// decimal numbers or the denominator is zero.
+fn evaluate_fraction(value: &str) -> Option<f64> {
+    let (numerator, denominator) = value.split_once('/')?;
+    let numerator: f64 = numerator.trim().parse().ok()?;
+    let denominator: f64 = denominator.trim().parse().ok()?;
+    if denominator == 0.0 {
+        return None;
+    }
+    Some(numerator / denominator)
+}
+
+/// Node and edge attributes set within a subgraph, which apply to all nodes and edges declared
+/// after them within the same subgraph.
+#[derive(Debug, Clone, Default)]
+struct Scope {
+    node_attributes: HashMap<String, AttrValue>,
+    edge_attributes: HashMap<String, AttrValue>,
+}
+
+/// A subgraph referencing its nodes by id, as they aren't part of the graph yet.
+#[derive(Debug, Default)]
+struct SubgraphIds {
+    id: String,
+    attributes: HashMap<String, AttrValue>,
+    nodes: Vec<String>,
+    subgraphs: Vec<SubgraphIds>,
+}
+
+impl SubgraphIds {
+    fn resolve(self, node_indices: &HashMap<String, NodeIndex>) -> Subgraph {
+        let mut seen = HashSet::new();
+        let nodes = self.nodes.iter()
+            .filter(|id| seen.insert(*id))
+            .map(|id| node_indices[id])
+            .collect();
+        let mut subgraph = Subgraph::new(self.id, self.attributes, nodes);
+        subgraph.subgraphs = self.subgraphs.into_iter()
+            .map(|child| child.resolve(node_indices))
+            .collect();
+        subgraph
+    }
+}
+
+/// All nodes and edges of a graph with its subgraphs flattened into it.
+#[derive(Debug, Default)]
+struct Flattened {
+    node_attributes: HashMap<String, AttrValue>,
+    edge_attributes: HashMap<String, AttrValue>,
+    nodes: Vec<Node>,
+    edges: Vec<EdgeInternal>,
+}
+
+impl Flattened {
+    /// Adds the statements of `parent`, which is the graph itself if `scope` is `None`.
+    ///
+    /// Nodes and edges of subgraphs are added to the graph as well and recorded as members of
+    /// all subgraphs containing them.
+    /// Subgraphs without id only scope attributes and aren't recorded.
+    fn flatten(&mut self, statements: Vec<Statement>, options: &ParseOptions, mut scope: Option<Scope>, parent: &mut SubgraphIds) {
+        for stmt in statements {
+            match stmt {
+                Statement::GlobalAttribute(mut attr) => {
+                    options.normalize_attributes(&mut attr.attributes);
+                    let attributes = match (attr._type, &mut scope) {
+                        (AttributeType::Graph, _) => &mut parent.attributes,
+                        (AttributeType::Node, Some(scope)) => &mut scope.node_attributes,
+                        (AttributeType::Node, None) => &mut self.node_attributes,
+                        (AttributeType::Edge, Some(scope)) => &mut scope.edge_attributes,
+                        (AttributeType::Edge, None) => &mut self.edge_attributes,
+                    };
+                    attributes.extend(attr.attributes.drain());
+                }
+                Statement::Node(mut node) => {
+                    options.normalize_attributes(&mut node.attributes);
+                    if let Some(scope) = &scope {
+                        node.attributes = scope.node_attributes.clone().into_iter().chain(node.attributes).collect();
+                    }
+                    parent.nodes.push(node.id.clone());
+                    self.nodes.push(node);
+                }
+                Statement::Edge(mut edge) => {
+                    options.normalize_attributes(&mut edge.attributes);
+                    if let Some(scope) = &scope {
+                        edge.attributes = scope.edge_attributes.clone().into_iter().chain(edge.attributes).collect();
+                    }
+                    parent.nodes.extend(edge.nodes.iter().cloned());
+                    self.edges.push(edge);
+                }
+                Statement::Subgraph(subgraph) => {
+                    let mut child = SubgraphIds {
+                        id: subgraph.id.clone().unwrap_or_default(),
+                        ..SubgraphIds::default()
+                    };
+                    self.flatten(subgraph.statements, options, Some(scope.clone().unwrap_or_default()), &mut child);
+                    parent.nodes.extend(child.nodes.iter().cloned());
+                    match subgraph.id {
+                        Some(_) => parent.subgraphs.push(child),
+                        None => parent.subgraphs.append(&mut child.subgraphs),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Parses a dot language graph into a DotGraph according to the
+/// given options, returning an error if the graph isn't valid dot.
+pub fn parse_with_options(s: &str, options: &ParseOptions) -> Result<DotGraph, ParseError> {
+    debug!("parsing str to DotGraph");
+    let graph_internal: GraphInternal = graph(s)?;
+    debug!("Converting statements into values");
+    let mut flattened = Flattened::default();
+    let mut root = SubgraphIds::default();
+    flattened.flatten(graph_internal.statements, options, None, &mut root);
+    let Flattened { node_attributes, edge_attributes, nodes, edges } = flattened;
 
     let mut graph = Graph::new();
-    let mut node_id_set = HashSet::new();
+    let mut node_indices = HashMap::new();
     debug!("Adding all node definitions to Graph");
     for node in nodes {
-        if !node_id_set.contains(&node.id) {
-            node_id_set.insert(node.id.clone());
-            graph.add_node(node);
+        match node_indices.get(&node.id) {
+            None => {
+                node_indices.insert(node.id.clone(), graph.add_node(node));
+            }
+            Some(&node_idx) => match options.duplicate_nodes {
+                DuplicateNodePolicy::FirstWins => debug!("ignoring duplicate declaration of {}", node.id),
+                DuplicateNodePolicy::LastWins => graph[node_idx].attributes = node.attributes,
+                DuplicateNodePolicy::MergeAttributes => graph[node_idx].attributes.extend(node.attributes),
+            },
         }
     }
     // Graphviz doesn't require all nodes to be defined beforehand.
@@ -92,39 +428,52 @@ pub fn parse(s: &str) -> DotGraph {
     debug!("Adding nodes from edge-definitions to graph");
     for edge in &edges {
         for node_id in &edge.nodes {
-            if !node_id_set.contains(node_id) {
-                node_id_set.insert(node_id.clone());
-                graph.add_node(Node {
+            if !node_indices.contains_key(node_id) {
+                let node_idx = graph.add_node(Node {
                     id: node_id.clone(),
                     attributes: Default::default(),
                 });
+                node_indices.insert(node_id.clone(), node_idx);
             }
         }
     }
+    let subgraphs = root.subgraphs.into_iter()
+        .map(|subgraph| subgraph.resolve(&node_indices))
+        .collect();
 
+    let edge_chain = options.edge_chain;
     let edge_fn = move |graph: &DotGraph| {
         edges.iter()
             .flat_map(|e| {
-                let attributes = &e.attributes;
-                e.nodes.iter()
-                    .zip(e.nodes.iter().skip(1))
-                    .map(move |(source, target)| (
-                        Edge::new(attributes.clone()),
-                        *graph.id_map().get(source).unwrap(),
-                        *graph.id_map().get(target).unwrap(),
-                    ))
+                let nodes = &e.nodes;
+                edge_chain.pairs(nodes.len()).into_iter()
+                    .map(move |(source, target)| {
+                        let mut attributes = e.attributes.clone();
+                        let ports = [("tailport", &e.ports[source]), ("headport", &e.ports[target])];
+                        for &(key, port) in &ports {
+                            if let Some(port) = port {
+                                attributes.insert(key.to_string(), port.clone().into());
+                            }
+                        }
+                        (
+                            Edge::new(attributes),
+                            *graph.id_map().get(&nodes[source]).unwrap(),
+                            *graph.id_map().get(&nodes[target]).unwrap(),
+                        )
+                    })
             }).collect()
     };
 
-    DotGraphBuilder::new(graph_internal._type)
+    Ok(DotGraphBuilder::new(graph_internal._type)
         .strict(graph_internal.strict)
         .id(graph_internal.id)
-        .graph_attributes(graph_attributes)
+        .graph_attributes(root.attributes)
         .node_attributes(node_attributes)
         .edge_attributes(edge_attributes)
         .graph(graph)
+        .subgraphs(subgraphs)
         .edges_fn(edge_fn)
-        .build()
+        .build())
 }
 
 #[test]
@@ -156,3 +505,326 @@ fn test_id() {
     assert_eq!(id(".42").unwrap(), ".42");
     assert_eq!(id("322.69").unwrap(), "322.69");
 }
+
+#[test]
+fn test_statements() {
+    let stmts: Vec<_> = statements("digraph { node [shape=box]; a; b [label=x]; a -> b -> c }").unwrap().collect();
+    assert_eq!(stmts.len(), 4);
+    match &stmts[0] {
+        Statement::GlobalAttribute(attr) => assert_eq!(attr.attribute_type(), AttributeType::Node),
+        stmt => panic!("expected global attribute, got {:?}", stmt),
+    }
+    match (&stmts[1], &stmts[2]) {
+        (Statement::Node(a), Statement::Node(b)) => assert_eq!((&*a.id, &*b.id), ("a", "b")),
+        other => panic!("expected two nodes, got {:?}", other),
+    }
+    match &stmts[3] {
+        Statement::Edge(edge) => assert_eq!(edge.nodes(), ["a", "b", "c"]),
+        stmt => panic!("expected edge, got {:?}", stmt),
+    }
+    assert!(statements("digraph { a -> }").is_err());
+}
+
+#[test]
+fn test_try_parse() {
+    assert_eq!(try_parse("digraph { a -> b }").unwrap().edge_count(), 1);
+    let err = try_parse("digraph {\n  a -> \n}").unwrap_err();
+    assert_eq!((err.line, err.column), (3, 1));
+}
+
+#[test]
+fn test_mixed_attribute_values() {
+    let graph = parse(r#"digraph { a [label=<<b>iron</b> plate> width=2.0 color="red"] }"#);
+    let a = &graph[graph.id_map()["a"]];
+    assert_eq!(a.attributes["label"], "<<b>iron</b> plate>");
+    assert_eq!(a.attributes["width"], "2.0");
+    assert_eq!(a.attributes["color"], "red");
+}
+
+#[test]
+fn test_html() {
+    assert_eq!(html("<<b>foo</b>>").unwrap(), "<<b>foo</b>>");
+    assert!(html("<<b>foo</b>").is_err());
+}
+
+#[test]
+fn test_whitespace_separated_attributes() {
+    let graph = parse("digraph { a -> b [label=foo color=bar width=2] }");
+    let edge = &graph.raw_edges()[0].weight;
+    assert_eq!(edge.attributes.len(), 3);
+    assert_eq!(edge.attributes["label"], "foo");
+    assert_eq!(edge.attributes["color"], "bar");
+    assert_eq!(edge.attributes["width"], "2");
+}
+
+#[test]
+fn test_quoted_ids_with_whitespace() {
+    let graph = parse(r#"digraph {
+        "Iron Plate" [label="iron"]
+        "Copper \"Cable\"" [label="cable"]
+        "Iron Plate" -> "Copper \"Cable\""
+    }"#);
+    assert_eq!(graph.node_count(), 2);
+    let id_map = graph.id_map();
+    let iron = id_map["Iron Plate"];
+    let cable = id_map["Copper \"Cable\""];
+    assert_eq!(graph[iron].attributes["label"], "iron");
+    assert_eq!(graph[cable].attributes["label"], "cable");
+    assert!(graph.contains_edge(iron, cable));
+}
+
+#[test]
+fn test_edge_chain_modes() {
+    let edges = |edge_chain| {
+        let options = ParseOptions { edge_chain, ..ParseOptions::default() };
+        let graph = parse_with_options("digraph { a -> b -> c [label=x] }", &options).unwrap();
+        assert!(graph.raw_edges().iter().all(|edge| edge.weight.attributes["label"] == "x"));
+        let mut edges: Vec<_> = graph.raw_edges().iter()
+            .map(|edge| (graph[edge.source()].id.clone(), graph[edge.target()].id.clone()))
+            .collect();
+        edges.sort();
+        edges
+    };
+    let pairs = |pairs: &[(&str, &str)]| -> Vec<_> { pairs.iter().map(|&(a, b)| (a.to_string(), b.to_string())).collect() };
+    assert_eq!(edges(EdgeChainMode::Sequential), pairs(&[("a", "b"), ("b", "c")]));
+    assert_eq!(edges(EdgeChainMode::StarFromHead), pairs(&[("a", "b"), ("a", "c")]));
+    assert_eq!(edges(EdgeChainMode::StarToTail), pairs(&[("a", "c"), ("b", "c")]));
+}
+
+#[test]
+fn test_lowercase_keys() {
+    let dot = r#"digraph { node [Color=red]; a [Label="x" Shape=box]; b [LABEL="y" label="z"]; a -> b [Label="e"] }"#;
+    assert!(!parse(dot).label_map().contains_key("x"));
+
+    let options = ParseOptions {
+        lowercase_keys: vec!["label".to_string(), "color".to_string()],
+        ..ParseOptions::default()
+    };
+    let graph = parse_with_options(dot, &options).unwrap();
+    let a = graph.id_map()["a"];
+    assert_eq!(graph.label_map()["x"], a);
+    assert_eq!(graph.label_map()["z"], graph.id_map()["b"]);
+    assert!(graph[a].attributes.contains_key("Shape"));
+    assert_eq!(graph.node_attributes["color"], "red");
+    assert_eq!(graph.raw_edges()[0].weight.attributes["label"], "e");
+}
+
+#[test]
+fn test_fraction_keys() {
+    let dot = r#"digraph { a -> b [amount="1/3"]; a -> c [amount=2, label="1/2"]; a -> d [amount="x/2"] }"#;
+    let amounts = |graph: &DotGraph| -> Vec<_> {
+        graph.raw_edges().iter().map(|edge| edge.weight.amount()).collect()
+    };
+    // without evaluation, the fraction isn't a number
+    assert_eq!(amounts(&parse(dot)), vec![1.0, 2.0, 1.0]);
+
+    let options = ParseOptions {
+        fraction_keys: vec!["amount".to_string()],
+        ..ParseOptions::default()
+    };
+    let graph = parse_with_options(dot, &options).unwrap();
+    let amounts = amounts(&graph);
+    assert!((amounts[0] - 0.333).abs() < 0.001);
+    assert_eq!(amounts[1..], [2.0, 1.0]);
+    assert_eq!(graph.raw_edges()[1].weight.attributes["amount"], "2");
+    assert_eq!(graph.raw_edges()[1].weight.attributes["label"], "1/2");
+    assert_eq!(graph.raw_edges()[2].weight.attributes["amount"], "x/2");
+}
+
+#[test]
+fn test_evaluate_fraction() {
+    assert_eq!(evaluate_fraction("3/4"), Some(0.75));
+    assert_eq!(evaluate_fraction(" 1 / 2 "), Some(0.5));
+    assert_eq!(evaluate_fraction("1/0"), None);
+    assert_eq!(evaluate_fraction("0/0"), None);
+    assert_eq!(evaluate_fraction("1"), None);
+}
+
+#[test]
+fn test_ports() {
+    assert_eq!(port(":e").unwrap(), "e");
+    assert_eq!(port(": in : sw").unwrap(), "in:sw");
+    assert_eq!(port(r#":"out 1""#).unwrap(), "out 1");
+    assert!(port(":").is_err());
+    assert_eq!(node_id(r#""iron-gear":e"#).unwrap(), ("iron-gear".to_string(), Some("e".to_string())));
+    assert_eq!(node_id("a").unwrap(), ("a".to_string(), None));
+
+    let edge = edge_stmt("a:out -> b -> c:in:w [label=x]").unwrap();
+    assert_eq!(edge.nodes(), ["a", "b", "c"]);
+    assert_eq!(edge.ports(), [Some("out".to_string()), None, Some("in:w".to_string())]);
+
+    let graph = parse(r#"digraph { "iron-gear":e -> "transport-belt":w; a:n -> b [tailport=s]; a:x [shape=box] }"#);
+    let id_map = graph.id_map();
+    assert_eq!(graph.node_count(), 4);
+    assert_eq!(graph[id_map["a"]].attributes["shape"], "box");
+    let belt = &graph[graph.find_edge(id_map["iron-gear"], id_map["transport-belt"]).unwrap()];
+    assert_eq!(belt.attributes["tailport"], "e");
+    assert_eq!(belt.attributes["headport"], "w");
+    let ab = &graph[graph.find_edge(id_map["a"], id_map["b"]).unwrap()];
+    assert_eq!(ab.attributes["tailport"], "n");
+    assert!(!ab.attributes.contains_key("headport"));
+
+    let mut written = Vec::new();
+    graph.write(&mut written).unwrap();
+    let reparsed = parse(std::str::from_utf8(&written).unwrap());
+    let edges = |graph: &DotGraph| {
+        let mut edges: Vec<_> = graph.raw_edges().iter()
+            .map(|edge| (graph[edge.source()].id.clone(), graph[edge.target()].id.clone(), edge.weight.attributes.clone()))
+            .collect();
+        edges.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        edges
+    };
+    assert_eq!(edges(&graph), edges(&reparsed));
+}
+
+#[test]
+fn test_anonymous_blocks() {
+    let graph = parse("digraph {
+        { node [shape=box]; a; { node [color=red] b } }
+        c
+        a -> c
+    }");
+    let id_map = graph.id_map();
+    assert_eq!(graph[id_map["a"]].attributes["shape"], "box");
+    assert!(!graph[id_map["a"]].attributes.contains_key("color"));
+    assert_eq!(graph[id_map["b"]].attributes["shape"], "box");
+    assert_eq!(graph[id_map["b"]].attributes["color"], "red");
+    assert!(graph[id_map["c"]].attributes.is_empty());
+    assert!(graph.node_attributes.is_empty());
+    assert!(graph.subgraphs.is_empty());
+    assert_eq!(graph.edge_count(), 1);
+}
+
+#[test]
+fn test_subgraphs() {
+    let graph = parse("digraph {
+        a
+        subgraph cluster_x { label=X; node [shape=box]; b; b -> c; subgraph cluster_y { d } }
+        subgraph { rank=same; e }
+        b -> a
+    }");
+    let id_map = graph.id_map();
+    assert_eq!(graph.node_count(), 5);
+    assert_eq!(graph.edge_count(), 2);
+    assert!(graph.graph_attributes.is_empty());
+    assert_eq!(graph.subgraphs.len(), 1);
+    let x = &graph.subgraphs[0];
+    assert_eq!(x.id, "cluster_x");
+    assert_eq!(x.attributes["label"], "X");
+    assert_eq!(x.nodes, vec![id_map["b"], id_map["c"], id_map["d"]]);
+    assert_eq!(x.subgraphs.len(), 1);
+    assert_eq!(x.subgraphs[0].nodes, vec![id_map["d"]]);
+    assert_eq!(graph[id_map["b"]].attributes["shape"], "box");
+    assert!(!graph[id_map["a"]].attributes.contains_key("shape"));
+}
+
+#[test]
+fn test_nested_subgraphs_round_trip() {
+    let graph = parse("digraph {
+        subgraph cluster_outer {
+            label=Outer
+            a -> b
+            subgraph cluster_inner { node [shape=box]; c; c -> d }
+        }
+        e -> a
+    }");
+    let mut written = Vec::new();
+    graph.write(&mut written).unwrap();
+    let reparsed = parse(std::str::from_utf8(&written).unwrap());
+
+    let ids = |graph: &DotGraph, nodes: &[NodeIndex]| {
+        let mut ids: Vec<_> = nodes.iter().map(|&node| graph[node].id.clone()).collect();
+        ids.sort();
+        ids
+    };
+    let edges = |graph: &DotGraph| {
+        let mut edges: Vec<_> = graph.raw_edges().iter()
+            .map(|edge| (graph[edge.source()].id.clone(), graph[edge.target()].id.clone()))
+            .collect();
+        edges.sort();
+        edges
+    };
+    for graph in &[&graph, &reparsed] {
+        let all: Vec<_> = graph.node_indices().collect();
+        assert_eq!(ids(graph, &all), ["a", "b", "c", "d", "e"]);
+        assert_eq!(graph.subgraphs.len(), 1);
+        let outer = &graph.subgraphs[0];
+        assert_eq!(outer.id, "cluster_outer");
+        assert_eq!(outer.attributes["label"], "Outer");
+        assert_eq!(ids(graph, &outer.nodes), ["a", "b", "c", "d"]);
+        assert_eq!(outer.subgraphs.len(), 1);
+        assert_eq!(outer.subgraphs[0].id, "cluster_inner");
+        assert_eq!(ids(graph, &outer.subgraphs[0].nodes), ["c", "d"]);
+        assert_eq!(graph[graph.id_map()["c"]].attributes["shape"], "box");
+    }
+    assert_eq!(edges(&graph), edges(&reparsed));
+}
+
+#[test]
+fn test_semicolon_separated_statements() {
+    let graph = parse("digraph { a; b; c }");
+    assert_eq!(graph.node_count(), 3);
+    assert_eq!(graph.edge_count(), 0);
+    assert_eq!(parse("digraph {a;b;c;}").node_count(), 3);
+    // whitespace before the semicolon
+    let graph = parse("digraph { node [shape=box] ; rank=same ; a -> b [x=y] ; subgraph s { c } ; d }");
+    assert_eq!(graph.node_count(), 4);
+    assert_eq!(graph.graph_attributes["rank"], "same");
+}
+
+#[test]
+fn test_quoted_attribute_keys() {
+    let graph = parse(r#"digraph { a ["weird key"="v", "esc\"aped"=w]; graph ["graph key"=x]; a -> b ["edge key"=y] }"#);
+    let a = &graph[graph.id_map()["a"]];
+    assert_eq!(a.attributes["weird key"], "v");
+    assert_eq!(a.attributes["esc\"aped"], "w");
+    assert_eq!(graph.graph_attributes["graph key"], "x");
+    assert_eq!(graph.raw_edges()[0].weight.attributes["edge key"], "y");
+
+    let mut written = Vec::new();
+    graph.write(&mut written).unwrap();
+    let reparsed = parse(std::str::from_utf8(&written).unwrap());
+    assert_eq!(reparsed[reparsed.id_map()["a"]].attributes, a.attributes);
+}
+
+#[test]
+fn test_duplicate_node_policy() {
+    let dot = "digraph { a [color=red, shape=box]; a -> b; a [color=blue, label=A] }";
+    let attributes = |duplicate_nodes| {
+        let graph = parse_with_options(dot, &ParseOptions { duplicate_nodes, ..ParseOptions::default() }).unwrap();
+        let mut attributes: Vec<_> = graph[graph.id_map()["a"]].attributes.clone().into_iter().collect();
+        attributes.sort();
+        attributes
+    };
+    let pairs = |pairs: &[(&str, &str)]| pairs.iter().map(|&(k, v)| (k.to_string(), v.into())).collect::<Vec<(_, AttrValue)>>();
+    assert_eq!(attributes(DuplicateNodePolicy::FirstWins), pairs(&[("color", "red"), ("shape", "box")]));
+    assert_eq!(attributes(DuplicateNodePolicy::LastWins), pairs(&[("color", "blue"), ("label", "A")]));
+    assert_eq!(attributes(DuplicateNodePolicy::MergeAttributes), pairs(&[("color", "blue"), ("label", "A"), ("shape", "box")]));
+}
+
+#[test]
+fn test_parse_lenient() {
+    let dot = "digraph {\n  a -> b\n  c -> -> d\n  subgraph x { e [label=] }\n  f -> g; h [label=\"}\"]\n}";
+    let (graph, errors) = parse_lenient(dot);
+    assert_eq!(errors.iter().map(|e| e.line).collect::<Vec<_>>(), vec![3, 4]);
+    let graph = graph.unwrap();
+    let mut ids: Vec<_> = graph.node_indices().map(|ix| graph[ix].id.as_str()).collect();
+    ids.sort();
+    assert_eq!(ids, vec!["a", "b", "f", "g", "h"]);
+    assert_eq!(graph.edge_count(), 2);
+    assert_eq!(graph.subgraphs[0].id, "x");
+
+    // positions after a blanked statement with multi-byte characters stay the same
+    let dot = "digraph {\n  \"ä\" -> -> b\n  c -> -> d\n}";
+    let (_, errors) = parse_lenient(dot);
+    let (_, ascii_errors) = parse_lenient(&dot.replace('ä', "ae"));
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[1].offset, ascii_errors[1].offset);
+    assert_eq!(errors[1].line, 3);
+
+    let (graph, errors) = parse_lenient("digraph { a -> b }");
+    assert!(graph.is_some() && errors.is_empty());
+    let (graph, errors) = parse_lenient("digraph { a -> b");
+    assert!(graph.is_none());
+    assert_eq!(errors.len(), 1);
+}
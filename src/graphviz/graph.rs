@@ -1,9 +1,14 @@
 use std::io::{Write, Result};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::cell::{Ref, RefCell};
 use std::ops::{Deref, DerefMut};
 
 use petgraph::graph::{self, DiGraph, DefaultIx};
+use petgraph::unionfind::UnionFind;
+use petgraph::Direction;
 use petgraph::visit::EdgeRef;
 
 /// Type alias for the graph representation of petgraph's graph used in this module.
@@ -22,6 +27,78 @@ pub enum GraphType {
     Digraph,
 }
 
+/// Value of an attribute, remembering whether it's an HTML string like `<<b>x</b>>`.
+///
+/// `AttrValue` derefs into the value as `str`, such that it can be used like a string.
+#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub enum AttrValue {
+    /// An identifier, number or quoted string, which is written as quoted string.
+    Quoted(String),
+    /// An HTML string including its outermost angle brackets, which is written as is.
+    Html(String),
+}
+
+impl AttrValue {
+    /// Returns the value as string, which includes the outermost angle brackets of HTML strings.
+    pub fn as_str(&self) -> &str {
+        match self {
+            AttrValue::Quoted(value) | AttrValue::Html(value) => value,
+        }
+    }
+
+    /// Returns whether this value is an HTML string.
+    pub fn is_html(&self) -> bool {
+        match self {
+            AttrValue::Quoted(_) => false,
+            AttrValue::Html(_) => true,
+        }
+    }
+}
+
+impl Deref for AttrValue {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Display for AttrValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<String> for AttrValue {
+    fn from(value: String) -> AttrValue {
+        AttrValue::Quoted(value)
+    }
+}
+
+impl<'a> From<&'a str> for AttrValue {
+    fn from(value: &'a str) -> AttrValue {
+        AttrValue::Quoted(value.to_string())
+    }
+}
+
+impl PartialEq<str> for AttrValue {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<'a> PartialEq<&'a str> for AttrValue {
+    fn eq(&self, other: &&'a str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for AttrValue {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
 /// A node inside the graph.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Node {
@@ -29,17 +106,37 @@ pub struct Node {
     pub id: String,
     /// Attributes of this node as defined by the
     /// [dot language specification](http://www.graphviz.org/doc/info/lang.html).
-    pub attributes: HashMap<String, String>,
+    pub attributes: HashMap<String, AttrValue>,
 }
 
 impl Node {
     /// Creates a new node with given id and attributes.
-    pub fn new(id: String, attributes: HashMap<String, String>) -> Node {
+    pub fn new(id: String, attributes: HashMap<String, AttrValue>) -> Node {
         Node {
             id,
             attributes,
         }
     }
+
+    /// Returns the attribute with the given key parsed as number, or `None` if it's missing or
+    /// not a number.
+    pub fn attribute_f64(&self, key: &str) -> Option<f64> {
+        self.attributes.get(key).and_then(|value| value.parse().ok())
+    }
+
+    /// Adds the given style to the comma-separated `style` attribute of this node, unless it's
+    /// already part of it.
+    ///
+    /// Existing styles are preserved, e.g. adding `filled` to `dashed` results in `dashed,filled`.
+    pub fn add_style(&mut self, style: &str) {
+        let mut styles: Vec<_> = self.attributes.get("style")
+            .map(|styles| styles.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect())
+            .unwrap_or_default();
+        if !styles.iter().any(|s| s == style) {
+            styles.push(style.to_string());
+        }
+        self.attributes.insert("style".to_string(), styles.join(",").into());
+    }
 }
 
 /// An edge between two nodes inside the graph.
@@ -51,18 +148,98 @@ impl Node {
 pub struct Edge {
     /// Attributes of this edge as defined by the
     /// [dot languge specification](http://www.graphviz.org/doc/info/lang.html).
-    pub attributes: HashMap<String, String>,
+    pub attributes: HashMap<String, AttrValue>,
 }
 
 impl Edge {
     /// Creates a new edge with given attributes.
-    pub fn new(attributes: HashMap<String, String>) -> Edge {
+    pub fn new(attributes: HashMap<String, AttrValue>) -> Edge {
         Edge {
             attributes: attributes,
         }
     }
+
+    /// Returns the `amount` attribute of this edge, i.e., how much of the target is required
+    /// by the source, defaulting to 1 if it's missing or not a number.
+    pub fn amount(&self) -> f64 {
+        self.number("amount")
+    }
+
+    /// Returns the attribute `key` of this edge as number, defaulting to 1 if it's missing or
+    /// not a number.
+    pub fn number(&self, key: &str) -> f64 {
+        self.attributes.get(key)
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(1.0)
+    }
+
+    /// Returns whether this edge constrains the ranking of its endpoints, which is the case
+    /// unless its `constraint` attribute is `false`, `no` or `0` like in graphviz.
+    pub fn is_constraint(&self) -> bool {
+        match self.attributes.get("constraint") {
+            Some(constraint) => !["false", "no", "0"].iter().any(|no| constraint.eq_ignore_ascii_case(no)),
+            None => true,
+        }
+    }
+}
+
+/// A subgraph grouping nodes of a graph, e.g. a `subgraph cluster_0 { ... }` block.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Subgraph {
+    /// Id / name of the subgraph. Graphviz draws a box around subgraphs whose id starts with
+    /// `cluster`.
+    pub id: String,
+    /// Graph attributes of this subgraph, e.g. its `label`.
+    pub attributes: HashMap<String, AttrValue>,
+    /// Nodes belonging to this subgraph, including the nodes of nested subgraphs.
+    pub nodes: Vec<NodeIndex>,
+    /// Subgraphs nested within this subgraph.
+    pub subgraphs: Vec<Subgraph>,
+}
+
+impl Subgraph {
+    /// Creates a new subgraph with given id, attributes and member nodes without nested
+    /// subgraphs.
+    pub fn new(id: String, attributes: HashMap<String, AttrValue>, nodes: Vec<NodeIndex>) -> Subgraph {
+        Subgraph {
+            id,
+            attributes,
+            nodes,
+            subgraphs: Vec::new(),
+        }
+    }
+}
+
+/// Error returned if a node id transformation maps two nodes to the same id.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct IdCollisionError {
+    /// Id multiple nodes would have been mapped to.
+    pub id: String,
+}
+
+impl fmt::Display for IdCollisionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "multiple nodes mapped to id {:?}", self.id)
+    }
 }
 
+impl Error for IdCollisionError {}
+
+/// Error returned if an operation requires an acyclic graph, but the graph contains a cycle.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NotADag {
+    /// Id of a node on the cycle.
+    pub id: String,
+}
+
+impl fmt::Display for NotADag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "graph contains a cycle through {:?}", self.id)
+    }
+}
+
+impl Error for NotADag {}
+
 /// Wrapper around [`petgraph::DiGraph`] including [dot language](http://www.graphviz.org/doc/info/lang.html)
 /// specific fields and attributes.
 ///
@@ -79,24 +256,30 @@ pub struct DotGraph {
     /// Id / Name
     pub id: Option<String>,
     /// Global `graph` attributes
-    pub graph_attributes: HashMap<String, String>,
+    pub graph_attributes: HashMap<String, AttrValue>,
     /// Global `node` attributes
-    pub node_attributes: HashMap<String, String>,
+    pub node_attributes: HashMap<String, AttrValue>,
     /// Global `edge` attributes
-    pub edge_attributes: HashMap<String, String>,
+    pub edge_attributes: HashMap<String, AttrValue>,
+    /// Subgraphs declared in the graph, which reference their nodes by index.
+    ///
+    /// Removing nodes from the graph invalidates these indices.
+    pub subgraphs: Vec<Subgraph>,
     /// Internal wrapped petgraph graph
     graph: Graph,
     /// Map from labels to the node; lazily generated
     label_map: RefCell<Option<HashMap<String, NodeIndex>>>,
     /// Map from ids to the node; lazily generated
     id_map: RefCell<Option<HashMap<String, NodeIndex>>>,
+    /// Depth of each node; lazily generated
+    depths: RefCell<Option<HashMap<NodeIndex, usize>>>,
 }
 
 impl DotGraph {
     /// Creates a new graph given all attributes. Prefer using
     /// [`DotGraphBuilder`](struct.DotGraphBuilder.html) instead.
-    pub fn new(strict: bool, _type: GraphType, id: Option<String>, graph_attributes: HashMap<String, String>,
-               node_attributes: HashMap<String, String>, edge_attributes: HashMap<String, String>,
+    pub fn new(strict: bool, _type: GraphType, id: Option<String>, graph_attributes: HashMap<String, AttrValue>,
+               node_attributes: HashMap<String, AttrValue>, edge_attributes: HashMap<String, AttrValue>,
                graph: Graph) -> DotGraph {
         DotGraph {
             strict: strict,
@@ -105,9 +288,11 @@ impl DotGraph {
             graph_attributes: graph_attributes,
             node_attributes: node_attributes,
             edge_attributes: edge_attributes,
+            subgraphs: Vec::new(),
             graph: graph,
             label_map: RefCell::new(None),
             id_map: RefCell::new(None),
+            depths: RefCell::new(None),
         }
     }
 
@@ -121,7 +306,7 @@ impl DotGraph {
         }
         drop(label_map);
         let map = self.graph.node_indices()
-            .filter_map(|ix| self.graph[ix].attributes.get("label").map(|l| (l.clone(), ix)))
+            .filter_map(|ix| self.graph[ix].attributes.get("label").map(|l| (l.to_string(), ix)))
             .collect();
         *self.label_map.borrow_mut() = Some(map);
         Ref::map(self.label_map.borrow(), |opt| opt.as_ref().unwrap())
@@ -143,6 +328,219 @@ impl DotGraph {
         Ref::map(self.id_map.borrow(), |opt| opt.as_ref().unwrap())
     }
 
+    /// Returns the index of the node with the given graphviz id without building or using the
+    /// cache of [`id_map`](#method.id_map).
+    ///
+    /// This scans all nodes and is thus only useful for few lookups on huge graphs, where
+    /// holding the whole map isn't worth it.
+    pub fn node_index_by_id_uncached(&self, id: &str) -> Option<NodeIndex> {
+        self.graph.node_indices().find(|&ix| self.graph[ix].id == id)
+    }
+
+    /// Lazily returns the depth of each node as calculated by
+    /// [`scc_depth`](#method.scc_depth).
+    ///
+    /// If `deref_mut` is used, the depths will be recalculated lazily.
+    pub fn depths(&self) -> Ref<'_, HashMap<NodeIndex, usize>> {
+        let depths = self.depths.borrow();
+        if depths.is_some() {
+            return Ref::map(depths, |opt| opt.as_ref().unwrap());
+        }
+        drop(depths);
+        let map = self.scc_depth();
+        *self.depths.borrow_mut() = Some(map);
+        Ref::map(self.depths.borrow(), |opt| opt.as_ref().unwrap())
+    }
+
+    /// Returns the union of the attribute keys of all nodes.
+    pub fn node_attribute_keys(&self) -> BTreeSet<String> {
+        self.graph.node_indices()
+            .flat_map(|ix| self.graph[ix].attributes.keys().cloned())
+            .collect()
+    }
+
+    /// Returns the union of the attribute keys of all edges.
+    pub fn edge_attribute_keys(&self) -> BTreeSet<String> {
+        self.graph.raw_edges().iter()
+            .flat_map(|edge| edge.weight.attributes.keys().cloned())
+            .collect()
+    }
+
+    /// Returns all edges whose attribute `key` is set to `value`, in index order.
+    pub fn edges_with_attribute(&self, key: &str, value: &str) -> Vec<EdgeIndex> {
+        self.graph.edge_indices()
+            .filter(|&ix| self.graph[ix].attributes.get(key).map(AttrValue::as_str) == Some(value))
+            .collect()
+    }
+
+    /// Returns a hash of the contents of this graph, e.g. to detect changes.
+    ///
+    /// The fingerprint only depends on the ids and attributes of the graph, its nodes and edges,
+    /// but not on the order in which nodes and edges were added.
+    /// It's stable across runs, as it doesn't use a randomly seeded hasher.
+    pub fn fingerprint(&self) -> u64 {
+        let mut nodes: Vec<_> = self.graph.node_indices()
+            .map(|ix| (&self.graph[ix].id, sorted(&self.graph[ix].attributes)))
+            .collect();
+        nodes.sort();
+        let mut edges: Vec<_> = self.graph.edge_references()
+            .map(|edge| (&self.graph[edge.source()].id, &self.graph[edge.target()].id, sorted(&edge.weight().attributes)))
+            .collect();
+        edges.sort();
+
+        let mut hasher = Fnv1a::default();
+        (self.strict, self._type, &self.id).hash(&mut hasher);
+        sorted(&self.graph_attributes).hash(&mut hasher);
+        sorted(&self.node_attributes).hash(&mut hasher);
+        sorted(&self.edge_attributes).hash(&mut hasher);
+        nodes.hash(&mut hasher);
+        edges.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns a copy of this graph with every edge flipped, such that consumers of an item
+    /// become its outgoing neighbors.
+    ///
+    /// Node indices, attributes and the graph type are preserved.
+    pub fn reversed(&self) -> DotGraph {
+        let mut reversed = self.clone();
+        reversed.reverse();
+        reversed
+    }
+
+    /// Returns a copy of this graph with all edges removed which are implied by other paths,
+    /// i.e., the graph with the fewest edges having the same reachability.
+    ///
+    /// If A depends on B and C, but B already depends on C, the edge from A to C is removed.
+    /// Of multiple parallel edges only the first one is kept.
+    /// Node indices and all other attributes are preserved.
+    /// Edges are treated as directed even in undirected graphs.
+    pub fn transitive_reduction(&self) -> ::std::result::Result<DotGraph, NotADag> {
+        if let Err(cycle) = petgraph::algo::toposort(&self.graph, None) {
+            return Err(NotADag { id: self.graph[cycle.node_id()].id.clone() });
+        }
+        let mut removed = HashSet::new();
+        for edge in self.graph.edge_references() {
+            // in a DAG, any other path to the target makes the edge redundant
+            let mut stack = vec![edge.source()];
+            let mut visited = HashSet::new();
+            while let Some(ix) = stack.pop() {
+                if !visited.insert(ix) {
+                    continue;
+                }
+                for other in self.graph.edges(ix) {
+                    // later parallel edges don't make the edge redundant, such that the first is kept
+                    let later_parallel = ix == edge.source() && other.target() == edge.target() && other.id() > edge.id();
+                    if other.id() == edge.id() || later_parallel || removed.contains(&other.id()) {
+                        continue;
+                    }
+                    if other.target() == edge.target() {
+                        removed.insert(edge.id());
+                        stack.clear();
+                        break;
+                    }
+                    stack.push(other.target());
+                }
+            }
+        }
+        let mut reduced = self.clone();
+        reduced.retain_edges(|_, ix| !removed.contains(&ix));
+        Ok(reduced)
+    }
+
+    /// Returns a copy of this graph keeping only the edges of a maximum spanning forest of its
+    /// undirected projection, i.e., the strongest links connecting each connected component.
+    ///
+    /// Edges are weighted by their [`amount`](struct.Edge.html#method.amount). Ties are broken
+    /// in favor of the lower edge index.
+    /// Kept edges retain their direction and attributes, and node indices are preserved.
+    /// Each connected component with `n` nodes keeps exactly `n-1` edges.
+    pub fn spanning_tree(&self) -> DotGraph {
+        let mut edges: Vec<_> = self.graph.edge_references().collect();
+        edges.sort_by(|a, b| b.weight().amount().total_cmp(&a.weight().amount()).then(a.id().cmp(&b.id())));
+        let mut components = UnionFind::new(self.graph.node_count());
+        let kept: HashSet<_> = edges.into_iter()
+            .filter(|edge| components.union(edge.source().index(), edge.target().index()))
+            .map(|edge| edge.id())
+            .collect();
+        let mut tree = self.clone();
+        tree.retain_edges(|_, ix| kept.contains(&ix));
+        tree
+    }
+
+    /// Applies the given transformation to the id of every node, e.g. to normalize ids.
+    ///
+    /// If two nodes would end up with the same id, an error is returned and the graph is left
+    /// unchanged.
+    pub fn map_node_ids(&mut self, f: impl Fn(&str) -> String) -> ::std::result::Result<(), IdCollisionError> {
+        let ids: Vec<_> = self.graph.node_indices()
+            .map(|ix| (ix, f(&self.graph[ix].id)))
+            .collect();
+        let mut seen = HashSet::new();
+        for (_, id) in &ids {
+            if !seen.insert(id) {
+                return Err(IdCollisionError { id: id.clone() });
+            }
+        }
+        for (ix, id) in ids {
+            self[ix].id = id;
+        }
+        Ok(())
+    }
+
+    /// Sets the `shape` attribute of every node according to its `category` attribute, e.g. to
+    /// draw fluids as ellipses and items as boxes.
+    ///
+    /// `mapping` maps categories to graphviz shapes. Nodes whose category isn't part of the
+    /// mapping, including nodes without a category, get the `default` shape, or are left
+    /// unchanged if there is none.
+    pub fn apply_category_shapes(&mut self, mapping: &HashMap<String, String>, default: Option<&str>) {
+        for node in self.graph.node_weights_mut() {
+            let shape = node.attributes.get("category")
+                .and_then(|category| mapping.get(category.as_str()))
+                .map(String::as_str)
+                .or(default);
+            if let Some(shape) = shape {
+                node.attributes.insert("shape".to_string(), shape.into());
+            }
+        }
+    }
+
+    /// Sets the `tooltip` attribute of every node to a summary of its dependencies and consumers,
+    /// which graphviz' SVG output shows on hover.
+    ///
+    /// Dependencies are listed with the number parsed from the `weight_key` attribute of their
+    /// edge, defaulting to 1, e.g. `needs: copper-plate ×1, iron-plate ×2`.
+    /// Consumers are listed in a second line like `used by: gear, pipe`.
+    /// Both lists are sorted by id. Nodes without any edges are left unchanged.
+    pub fn annotate_tooltips(&mut self, weight_key: &str) {
+        let tooltips: Vec<_> = self.graph.node_indices()
+            .map(|ix| {
+                let mut needs: Vec<_> = self.graph.edges_directed(ix, Direction::Outgoing)
+                    .map(|edge| (&self.graph[edge.target()].id, edge.weight().number(weight_key)))
+                    .collect();
+                needs.sort_by(|a, b| a.0.cmp(b.0));
+                let mut used_by: Vec<_> = self.graph.neighbors_directed(ix, Direction::Incoming)
+                    .map(|consumer| self.graph[consumer].id.as_str())
+                    .collect();
+                used_by.sort();
+                let mut lines = Vec::new();
+                if !needs.is_empty() {
+                    let needs: Vec<_> = needs.iter().map(|(id, weight)| format!("{} ×{}", id, weight)).collect();
+                    lines.push(format!("needs: {}", needs.join(", ")));
+                }
+                if !used_by.is_empty() {
+                    lines.push(format!("used by: {}", used_by.join(", ")));
+                }
+                (ix, lines.join("\n"))
+            }).collect();
+        for (ix, tooltip) in tooltips {
+            if !tooltip.is_empty() {
+                self.graph[ix].attributes.insert("tooltip".to_string(), tooltip.into());
+            }
+        }
+    }
+
     /// Writes this graph in a dot compatible format to given writer.
     ///
     /// This method can be used to save a `DotGraph` to a file.
@@ -158,6 +556,41 @@ impl DotGraph {
     /// graph.write(&mut file).unwrap();
     /// ```
     pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        self.write_filtered(writer, |_| true)
+    }
+
+    /// Writes this graph like [`write`](#method.write), but only emits attributes whose key
+    /// passes the given predicate.
+    ///
+    /// The predicate applies to every attribute list, i.e., to the global `graph`, `node` and
+    /// `edge` attributes as well as to the attributes of each node and edge.
+    /// This allows exporting a clean view of the graph without mutating it.
+    ///
+    /// # Example
+    /// ```ignore
+    /// // strip everything except labels and colors
+    /// graph.write_filtered(&mut file, |key| key == "label" || key == "color").unwrap();
+    /// ```
+    pub fn write_filtered<W: Write>(&self, writer: &mut W, keep: impl Fn(&str) -> bool) -> Result<()> {
+        self.write_internal(writer, &self.subgraphs, keep, &WriteOptions::default())
+    }
+
+    /// Writes this graph like [`write`](#method.write), but wraps the nodes of each given
+    /// subgraph in a `subgraph` block instead of the subgraphs of this graph.
+    ///
+    /// Nodes which aren't part of any subgraph are written at the top level.
+    /// If a node is part of multiple subgraphs, it's only written in the innermost first one.
+    pub fn write_with_subgraphs<W: Write>(&self, writer: &mut W, subgraphs: &[Subgraph]) -> Result<()> {
+        self.write_internal(writer, subgraphs, |_| true, &WriteOptions::default())
+    }
+
+    /// Writes this graph like [`write`](#method.write), formatted according to the given options.
+    pub fn write_with_options<W: Write>(&self, writer: &mut W, options: &WriteOptions) -> Result<()> {
+        self.write_internal(writer, &self.subgraphs, |_| true, options)
+    }
+
+    fn write_internal<W: Write>(&self, writer: &mut W, subgraphs: &[Subgraph], keep: impl Fn(&str) -> bool,
+                                options: &WriteOptions) -> Result<()> {
         if self.strict {
             write!(writer, "strict ")?;
         }
@@ -166,42 +599,37 @@ impl DotGraph {
             GraphType::Digraph => write!(writer, "digraph ")?,
         }
         if let Some(ref id) = self.id {
-            write!(writer, "{:?} ", id)?;
+            write!(writer, "{} ", quote(id))?;
         }
         writeln!(writer, "{{")?;
 
-        if !self.graph_attributes.is_empty() {
-            writeln!(writer, "  graph [")?;
-            for (ref key, ref value) in self.graph_attributes.iter() {
-                writeln!(writer, "    {} = {:?}", key, value)?;
-            }
-            writeln!(writer, "  ]")?;
-        }
-        if !self.node_attributes.is_empty() {
-            writeln!(writer, "  node [")?;
-            for (ref key, ref value) in self.node_attributes.iter() {
-                writeln!(writer, "    {} = {:?}", key, value)?;
-            }
-            writeln!(writer, "  ]")?;
-        }
-        if !self.edge_attributes.is_empty() {
-            writeln!(writer, "  edge [")?;
-            for (ref key, ref value) in self.edge_attributes.iter() {
-                writeln!(writer, "    {} = {:?}", key, value)?;
+        let global_attributes = [
+            ("graph", &self.graph_attributes),
+            ("node", &self.node_attributes),
+            ("edge", &self.edge_attributes),
+        ];
+        for (name, attributes) in global_attributes.iter() {
+            if attributes.keys().any(|key| keep(key)) {
+                write_statement(writer, options, 1, &format!("{} ", name), attributes, &keep)?;
             }
-            writeln!(writer, "  ]")?;
         }
 
-        for ix in self.graph.node_indices() {
+        let mut written = HashSet::new();
+        for subgraph in subgraphs {
+            self.write_subgraph(writer, subgraph, 1, &mut written, &keep, options)?;
+        }
+        for ix in self.graph.node_indices().filter(|ix| !written.contains(ix)) {
             let node = &self.graph[ix];
-            writeln!(writer, "  {:?} [", node.id)?;
-            for (ref key, ref value) in node.attributes.iter() {
-                writeln!(writer, "    {} = \"{}\"", key, value)?;
-            }
-            writeln!(writer, "  ]")?;
+            write_statement(writer, options, 1, &format!("{} ", quote(&node.id)), &node.attributes, &keep)?;
         }
 
-        for edgeref in self.graph.edge_references() {
+        let mut edges: Vec<_> = self.graph.edge_references().collect();
+        if options.sorted_edges {
+            edges.sort_by_cached_key(|edge| {
+                (&self.graph[edge.source()].id, &self.graph[edge.target()].id, sorted(&edge.weight().attributes))
+            });
+        }
+        for edgeref in edges {
             let edge = &self.graph[edgeref.id()];
             let source = &self.graph[edgeref.source()];
             let target = &self.graph[edgeref.target()];
@@ -209,12 +637,13 @@ impl DotGraph {
                 GraphType::Digraph => "->",
                 GraphType::Graph => "--",
             };
-            write!(writer, "  {:?} {} {:?}", source.id, edgeop, target.id)?;
-            writeln!(writer, "[")?;
-            for (ref key, ref value) in edge.attributes.iter() {
-                writeln!(writer, "    {} = {:?}", key, value)?;
-            }
-            writeln!(writer, "  ]")?;
+            // ports are part of the endpoints instead of the attribute list
+            let port = |key: &str| edge.attributes.get(key)
+                .filter(|_| keep(key))
+                .map(|port| format!(":{}", port_id(port)))
+                .unwrap_or_default();
+            let head = format!("{}{} {} {}{}", quote(&source.id), port("tailport"), edgeop, quote(&target.id), port("headport"));
+            write_statement(writer, options, 1, &head, &edge.attributes, |key| key != "tailport" && key != "headport" && keep(key))?;
         }
 
         writeln!(writer, "}}")?;
@@ -222,6 +651,149 @@ impl DotGraph {
     }
 }
 
+impl DotGraph {
+    /// Writes the subgraph and its nested subgraphs, skipping all nodes in `written` and adding
+    /// all written nodes to it.
+    ///
+    /// Nodes of nested subgraphs are written within the nested subgraph.
+    fn write_subgraph<W: Write>(&self, writer: &mut W, subgraph: &Subgraph, level: usize, written: &mut HashSet<NodeIndex>,
+                                keep: &dyn Fn(&str) -> bool, options: &WriteOptions) -> Result<()> {
+        let indent = options.indentation(level);
+        writeln!(writer, "{}subgraph {} {{", indent, quote(&subgraph.id))?;
+        if subgraph.attributes.keys().any(|key| keep(key)) {
+            write_statement(writer, options, level + 1, "graph ", &subgraph.attributes, keep)?;
+        }
+        for nested in &subgraph.subgraphs {
+            self.write_subgraph(writer, nested, level + 1, written, keep, options)?;
+        }
+        for &ix in &subgraph.nodes {
+            if written.insert(ix) {
+                let node = &self.graph[ix];
+                write_statement(writer, options, level + 1, &format!("{} ", quote(&node.id)), &node.attributes, keep)?;
+            }
+        }
+        writeln!(writer, "{}}}", indent)
+    }
+}
+
+/// Formatting options for [`DotGraph::write_with_options`](struct.DotGraph.html#method.write_with_options).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WriteOptions {
+    /// Number of spaces per indentation level.
+    pub indent: usize,
+    /// Write attribute lists on a single line like `a [label="x" color="y"]` instead of one
+    /// attribute per line.
+    pub attributes_inline: bool,
+    /// Write edges sorted by the ids of their source and target and their attributes instead of
+    /// in the order of their index, such that graphs with the same content are written
+    /// identically regardless of the order in which they were built, e.g. for diffing.
+    pub sorted_edges: bool,
+}
+
+impl WriteOptions {
+    fn indentation(&self, level: usize) -> String {
+        " ".repeat(self.indent * level)
+    }
+}
+
+impl Default for WriteOptions {
+    fn default() -> WriteOptions {
+        WriteOptions {
+            indent: 2,
+            attributes_inline: false,
+            sorted_edges: false,
+        }
+    }
+}
+
+/// Writes a statement consisting of `head` followed by the attribute list of all attributes
+/// passing `keep`, indented by `level`.
+fn write_statement<W: Write>(writer: &mut W, options: &WriteOptions, level: usize, head: &str,
+                             attributes: &HashMap<String, AttrValue>, keep: impl Fn(&str) -> bool) -> Result<()> {
+    let indent = options.indentation(level);
+    let attributes = attributes.iter().filter(|(key, _)| keep(key));
+    if options.attributes_inline {
+        let attributes: Vec<_> = attributes.map(|(key, value)| format!("{}={}", attribute_key(key), attribute_value(value))).collect();
+        writeln!(writer, "{}{}[{}]", indent, head, attributes.join(" "))
+    } else {
+        writeln!(writer, "{}{}[", indent, head)?;
+        let attribute_indent = options.indentation(level + 1);
+        for (key, value) in attributes {
+            writeln!(writer, "{}{} = {}", attribute_indent, attribute_key(key), attribute_value(value))?;
+        }
+        writeln!(writer, "{}]", indent)
+    }
+}
+
+/// Returns the attributes sorted by key.
+fn sorted(attributes: &HashMap<String, AttrValue>) -> BTreeMap<&String, &AttrValue> {
+    attributes.iter().collect()
+}
+
+/// 64 bit FNV-1a hasher, which unlike `DefaultHasher` is guaranteed to stay the same across
+/// Rust versions.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Fnv1a {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// Quotes the given string as a dot language double-quoted string.
+///
+/// Double quotes and backslashes are escaped with a backslash, which is exactly what the parser
+/// unescapes again.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Returns whether `s` is a plain identifier, which doesn't need to be quoted.
+fn is_identifier(s: &str) -> bool {
+    s.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Formats an attribute key, quoting it only if it isn't a plain identifier.
+fn attribute_key(key: &str) -> String {
+    if is_identifier(key) { key.to_string() } else { quote(key) }
+}
+
+/// Formats an attribute value, quoting it unless it's an HTML string like `<<b>x</b>>`.
+///
+/// HTML strings keep their outermost angle brackets when parsed, such that they are written back
+/// as HTML strings.
+fn attribute_value(value: &AttrValue) -> String {
+    match value {
+        AttrValue::Quoted(value) => quote(value),
+        AttrValue::Html(value) => value.clone(),
+    }
+}
+
+/// Formats a port like `port` or `port:compass_point` for use after a node id.
+///
+/// Each part is only quoted if it isn't a plain identifier, such that compass points stay
+/// recognizable.
+fn port_id(port: &str) -> String {
+    port.split(':')
+        .map(|part| if is_identifier(part) { part.to_string() } else { quote(part) })
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
 impl Deref for DotGraph {
     type Target = Graph;
 
@@ -234,6 +806,343 @@ impl DerefMut for DotGraph {
     fn deref_mut(&mut self) -> &mut Self::Target {
         self.id_map.borrow_mut().take();
         self.label_map.borrow_mut().take();
+        self.depths.borrow_mut().take();
         &mut self.graph
     }
 }
+
+#[test]
+fn test_escaped_label_round_trip() {
+    let graph = crate::graphviz::parse(r#"digraph { foo [label="a \"b\" c"] }"#);
+    let foo = graph.label_map()[r#"a "b" c"#];
+    assert_eq!(graph[foo].id, "foo");
+
+    let mut written = Vec::new();
+    graph.write(&mut written).unwrap();
+    let reparsed = crate::graphviz::parse(std::str::from_utf8(&written).unwrap());
+    let foo = reparsed.label_map()[r#"a "b" c"#];
+    assert_eq!(reparsed[foo].id, "foo");
+    assert_eq!(reparsed[foo].attributes, graph[graph.id_map()["foo"]].attributes);
+}
+
+#[test]
+fn test_map_node_ids() {
+    let mut graph = crate::graphviz::parse("digraph { Iron_Plate -> Iron_Ore }");
+    assert!(graph.id_map().contains_key("Iron_Plate"));
+    graph.map_node_ids(|id| id.to_lowercase()).unwrap();
+    let id_map = graph.id_map();
+    assert_eq!(id_map.len(), 2);
+    assert!(graph.contains_edge(id_map["iron_plate"], id_map["iron_ore"]));
+}
+
+#[test]
+fn test_map_node_ids_collision() {
+    let mut graph = crate::graphviz::parse("digraph { iron_plate -> Iron_Plate }");
+    let err = graph.map_node_ids(|id| id.to_lowercase()).unwrap_err();
+    assert_eq!(err, IdCollisionError { id: "iron_plate".to_string() });
+    assert!(graph.id_map().contains_key("Iron_Plate"));
+}
+
+#[test]
+fn test_write_filtered() {
+    let graph = crate::graphviz::parse(r#"digraph {
+        edge [amount=1]
+        a [label="A" amount=2 color=red]
+        a -> b [label="x" amount=3]
+    }"#);
+    let mut written = Vec::new();
+    graph.write_filtered(&mut written, |key| key == "label").unwrap();
+    let written = String::from_utf8(written).unwrap();
+    assert!(written.contains("label = \"A\""));
+    assert!(written.contains("label = \"x\""));
+    assert!(!written.contains("amount"));
+    assert!(!written.contains("color"));
+    assert!(!written.contains("edge ["));
+
+    let reparsed = crate::graphviz::parse(&written);
+    assert_eq!(reparsed.node_count(), 2);
+    assert_eq!(reparsed.edge_count(), 1);
+}
+
+#[test]
+fn test_attribute_keys() {
+    let graph = crate::graphviz::parse(r#"digraph {
+        node [shape=box]
+        a [label="A" color=red]
+        b [label="B" tier=2]
+        a -> b [amount=2]
+        b -> c [amount=1 style=dashed]
+    }"#);
+    let keys = |keys: &[&str]| keys.iter().map(|key| key.to_string()).collect::<BTreeSet<_>>();
+    assert_eq!(graph.node_attribute_keys(), keys(&["color", "label", "tier"]));
+    assert_eq!(graph.edge_attribute_keys(), keys(&["amount", "style"]));
+}
+
+#[test]
+fn test_write_ports() {
+    let graph = crate::graphviz::parse(r#"digraph { a -> b [tailport=e headport="in:w" label="x"]; b -> c }"#);
+    let mut written = Vec::new();
+    graph.write(&mut written).unwrap();
+    let written = String::from_utf8(written).unwrap();
+    assert!(written.contains("\"a\":e -> \"b\":in:w["), "{}", written);
+    assert!(written.contains("\"b\" -> \"c\"["), "{}", written);
+    assert!(!written.contains("tailport"));
+    assert!(!written.contains("headport"));
+    assert!(written.contains("label = \"x\""));
+
+    let mut rewritten = Vec::new();
+    graph.write(&mut rewritten).unwrap();
+    assert_eq!(written, String::from_utf8(rewritten).unwrap());
+}
+
+#[test]
+fn test_reversed() {
+    use petgraph::Direction;
+
+    let graph = crate::graphviz::parse("digraph { a -> b -> c; a -> c [label=x]; d -> c }");
+    let reversed = graph.reversed();
+    assert_eq!(reversed._type, graph._type);
+    assert_eq!(reversed.edge_count(), graph.edge_count());
+    for node in graph.node_indices() {
+        let incoming: HashSet<_> = graph.neighbors_directed(node, Direction::Incoming).collect();
+        let outgoing: HashSet<_> = reversed.neighbors_directed(node, Direction::Outgoing).collect();
+        assert_eq!(outgoing, incoming);
+    }
+    let c = reversed.id_map()["c"];
+    let edge = reversed.find_edge(c, reversed.id_map()["a"]).unwrap();
+    assert_eq!(reversed[edge].attributes["label"], "x");
+}
+
+#[test]
+fn test_depths_cache() {
+    let mut graph = crate::graphviz::parse("digraph { a -> b -> c }");
+    assert!(graph.depths.borrow().is_none());
+    let c = graph.id_map()["c"];
+    assert_eq!(graph.depths()[&c], 0);
+    assert_eq!(graph.depths()[&graph.id_map()["a"]], 2);
+    // the second query is served from the same cached map
+    let cached: *const HashMap<_, _> = {
+        let depths = graph.depths();
+        &*depths
+    };
+    assert!(std::ptr::eq(&*graph.depths(), cached));
+
+    let d = graph.add_node(Node::new("d".to_string(), HashMap::new()));
+    assert!(graph.depths.borrow().is_none());
+    graph.add_edge(c, d, Edge::new(HashMap::new()));
+    assert_eq!(graph.depths()[&graph.id_map()["a"]], 3);
+}
+
+#[test]
+fn test_write_options() {
+    let graph = crate::graphviz::parse(r#"digraph { a [label="x" color="y"]; a -> b [label="e"]; b }"#);
+    let write = |options: &WriteOptions| {
+        let mut written = Vec::new();
+        graph.write_with_options(&mut written, options).unwrap();
+        String::from_utf8(written).unwrap()
+    };
+    let multi_line = write(&WriteOptions { indent: 4, ..WriteOptions::default() });
+    assert!(multi_line.contains("\n    \"b\" [\n    ]\n"), "{}", multi_line);
+    assert!(multi_line.contains("\n        label = \"x\"\n"), "{}", multi_line);
+    let inline = write(&WriteOptions { attributes_inline: true, ..WriteOptions::default() });
+    assert!(inline.contains("\n  \"b\" []\n"), "{}", inline);
+    assert!(inline.contains("\n  \"a\" -> \"b\"[label=\"e\"]\n"), "{}", inline);
+
+    for written in &[multi_line, inline] {
+        let reparsed = crate::graphviz::parse(written);
+        assert_eq!(reparsed.node_count(), graph.node_count());
+        assert_eq!(reparsed.edge_count(), graph.edge_count());
+        for ix in graph.node_indices() {
+            assert_eq!(reparsed[reparsed.id_map()[&graph[ix].id]], graph[ix]);
+        }
+        assert_eq!(reparsed.raw_edges()[0].weight, graph.raw_edges()[0].weight);
+    }
+}
+
+#[test]
+fn test_add_style() {
+    let graph = crate::graphviz::parse(r#"digraph { a [style="dashed, bold"]; b }"#);
+    let mut a = graph[graph.id_map()["a"]].clone();
+    a.add_style("filled");
+    assert_eq!(a.attributes["style"], "dashed,bold,filled");
+    a.add_style("dashed");
+    assert_eq!(a.attributes["style"], "dashed,bold,filled");
+    let mut b = graph[graph.id_map()["b"]].clone();
+    b.add_style("filled");
+    assert_eq!(b.attributes["style"], "filled");
+}
+
+#[test]
+fn test_fingerprint() {
+    let dot = r#"digraph { a [label="A" color=red]; b; a -> b [amount=2]; c -> a }"#;
+    let graph = crate::graphviz::parse(dot);
+    assert_eq!(graph.fingerprint(), crate::graphviz::parse(dot).fingerprint());
+    assert_eq!(graph.fingerprint(), graph.clone().fingerprint());
+    // declaration order doesn't matter
+    let reordered = crate::graphviz::parse(r#"digraph { c -> a; b; a [color=red label="A"]; a -> b [amount=2] }"#);
+    assert_eq!(graph.fingerprint(), reordered.fingerprint());
+
+    let changed = crate::graphviz::parse(r#"digraph { a [label="A" color=blue]; b; a -> b [amount=2]; c -> a }"#);
+    assert_ne!(graph.fingerprint(), changed.fingerprint());
+    let reversed = graph.reversed();
+    assert_ne!(graph.fingerprint(), reversed.fingerprint());
+}
+
+#[test]
+fn test_write_subgraphs() {
+    let graph = crate::graphviz::parse(r#"digraph { subgraph cluster_a { label="A"; a; b } c; a -> c }"#);
+    let mut written = Vec::new();
+    graph.write(&mut written).unwrap();
+    let reparsed = crate::graphviz::parse(std::str::from_utf8(&written).unwrap());
+    assert_eq!(reparsed.subgraphs.len(), 1);
+    let subgraph = &reparsed.subgraphs[0];
+    assert_eq!(subgraph.id, "cluster_a");
+    assert_eq!(subgraph.attributes["label"], "A");
+    let ids: Vec<_> = subgraph.nodes.iter().map(|&node_idx| reparsed[node_idx].id.as_str()).collect();
+    assert_eq!(ids, vec!["a", "b"]);
+    assert_eq!(reparsed.edge_count(), 1);
+}
+
+#[test]
+fn test_transitive_reduction() {
+    use petgraph::algo::has_path_connecting;
+
+    let graph = crate::graphviz::parse("digraph { a -> b; b -> c [label=first]; a -> c [label=x]; a -> d -> c; c -> e; a -> e; b -> c [label=second] }");
+    let reduced = graph.transitive_reduction().unwrap();
+    let id_map = reduced.id_map();
+    assert!(!reduced.contains_edge(id_map["a"], id_map["c"]));
+    assert!(!reduced.contains_edge(id_map["a"], id_map["e"]));
+    assert_eq!(reduced.edge_count(), 5);
+    let bc = reduced.find_edge(id_map["b"], id_map["c"]).unwrap();
+    assert_eq!(reduced[bc].attributes["label"], "first");
+    for a in graph.node_indices() {
+        for b in graph.node_indices() {
+            assert_eq!(has_path_connecting(&*graph, a, b, None), has_path_connecting(&*reduced, a, b, None));
+        }
+    }
+
+    let err = crate::graphviz::parse("digraph { a -> b -> a }").transitive_reduction().unwrap_err();
+    assert!(err.id == "a" || err.id == "b");
+}
+
+#[test]
+fn test_annotate_tooltips() {
+    let mut graph = crate::graphviz::parse("digraph { gear -> plate [amount=2]; circuit -> plate; circuit -> cable [amount=3]; lonely }");
+    graph.annotate_tooltips("amount");
+    let id_map = graph.id_map();
+    assert_eq!(graph[id_map["circuit"]].attributes["tooltip"], "needs: cable ×3, plate ×1");
+    assert_eq!(graph[id_map["plate"]].attributes["tooltip"], "used by: circuit, gear");
+    assert_eq!(graph[id_map["gear"]].attributes["tooltip"], "needs: plate ×2");
+    assert!(!graph[id_map["lonely"]].attributes.contains_key("tooltip"));
+}
+
+#[test]
+fn test_spanning_tree() {
+    let graph = crate::graphviz::parse("digraph {
+        a -> b [amount=5]; b -> c [amount=1]; a -> c [amount=3]; c -> a [amount=2]
+        x -> y [amount=2]; y -> z [amount=4]; x -> z [amount=3]
+        lonely
+    }");
+    let tree = graph.spanning_tree();
+    let id_map = tree.id_map();
+    assert_eq!(tree.node_count(), graph.node_count());
+    assert_eq!(tree.edge_count(), 4);
+    assert!(tree.contains_edge(id_map["a"], id_map["b"]));
+    assert!(tree.contains_edge(id_map["a"], id_map["c"]));
+    assert!(tree.contains_edge(id_map["y"], id_map["z"]));
+    assert!(tree.contains_edge(id_map["x"], id_map["z"]));
+    assert_eq!(petgraph::algo::connected_components(&*tree), petgraph::algo::connected_components(&*graph));
+}
+
+#[test]
+fn test_edges_with_attribute() {
+    let graph = crate::graphviz::parse(r#"digraph {
+        a -> b [kind=fluid]
+        a -> c [kind=item]
+        b -> c [kind=fluid, amount=2]
+        c -> d
+    }"#);
+    let ids = |edges: Vec<EdgeIndex>| -> Vec<_> {
+        edges.into_iter()
+            .map(|ix| {
+                let (source, target) = graph.edge_endpoints(ix).unwrap();
+                (graph[source].id.as_str(), graph[target].id.as_str())
+            })
+            .collect()
+    };
+    assert_eq!(ids(graph.edges_with_attribute("kind", "fluid")), vec![("a", "b"), ("b", "c")]);
+    assert_eq!(ids(graph.edges_with_attribute("kind", "item")), vec![("a", "c")]);
+    assert!(graph.edges_with_attribute("kind", "steam").is_empty());
+    assert!(graph.edges_with_attribute("amount", "1").is_empty());
+}
+
+#[test]
+fn test_node_index_by_id_uncached() {
+    let graph = crate::graphviz::parse("digraph { a -> b -> c }");
+    let b = graph.node_index_by_id_uncached("b").unwrap();
+    assert_eq!(graph[b].id, "b");
+    assert_eq!(graph.node_index_by_id_uncached("d"), None);
+    assert!(graph.id_map.borrow().is_none());
+    assert_eq!(graph.id_map()["b"], b);
+}
+
+#[test]
+fn test_apply_category_shapes() {
+    let mut graph = crate::graphviz::parse(r#"digraph {
+        water [category=fluid]; steam [category=fluid]; plate [category=item, shape=circle]; gear
+        steam -> water; gear -> plate
+    }"#);
+    let mapping: HashMap<_, _> = vec![("fluid".to_string(), "ellipse".to_string())].into_iter().collect();
+    graph.apply_category_shapes(&mapping, None);
+    let shape = |graph: &DotGraph, id: &str| graph[graph.id_map()[id]].attributes.get("shape").cloned();
+    assert_eq!(shape(&graph, "water").as_deref(), Some("ellipse"));
+    assert_eq!(shape(&graph, "steam").as_deref(), Some("ellipse"));
+    assert_eq!(shape(&graph, "plate").as_deref(), Some("circle"));
+    assert_eq!(shape(&graph, "gear"), None);
+
+    graph.apply_category_shapes(&mapping, Some("box"));
+    assert_eq!(shape(&graph, "water").as_deref(), Some("ellipse"));
+    assert_eq!(shape(&graph, "plate").as_deref(), Some("box"));
+    assert_eq!(shape(&graph, "gear").as_deref(), Some("box"));
+}
+
+#[test]
+fn test_write_sorted_edges() {
+    let forward = crate::graphviz::parse("digraph { a; b; c; a -> b [amount=2]; a -> b [amount=1]; b -> c; a -> c }");
+    let backward = crate::graphviz::parse("digraph { a; b; c; a -> c; b -> c; a -> b [amount=1]; a -> b [amount=2] }");
+    let write = |graph: &DotGraph| {
+        let mut written = Vec::new();
+        graph.write_with_options(&mut written, &WriteOptions { sorted_edges: true, ..WriteOptions::default() }).unwrap();
+        String::from_utf8(written).unwrap()
+    };
+    assert_eq!(write(&forward), write(&backward));
+    let written = write(&forward);
+    let edges: Vec<_> = written.lines().filter(|line| line.contains("->")).collect();
+    assert_eq!(edges, vec![r#"  "a" -> "b"["#, r#"  "a" -> "b"["#, r#"  "a" -> "c"["#, r#"  "b" -> "c"["#]);
+    let amounts: Vec<_> = written.lines().filter(|line| line.contains("amount")).map(str::trim).collect();
+    assert_eq!(amounts, vec![r#"amount = "1""#, r#"amount = "2""#]);
+}
+
+#[test]
+fn test_write_html_labels() {
+    let graph = crate::graphviz::parse(r#"digraph { a [label=<<b>iron</b> plate>]; b [label="1 < 2"] }"#);
+    let mut written = Vec::new();
+    graph.write(&mut written).unwrap();
+    let written = String::from_utf8(written).unwrap();
+    assert!(written.contains("label = <<b>iron</b> plate>\n"), "{}", written);
+    assert!(written.contains("label = \"1 < 2\"\n"), "{}", written);
+    assert_eq!(crate::graphviz::parse(&written)[NodeIndex::new(0)], graph[NodeIndex::new(0)]);
+
+    // quoted strings looking like HTML stay quoted, even if the same HTML string is used as well
+    let graph = crate::graphviz::parse(r#"digraph { a [label="<x>"]; b [label=<x>] }"#);
+    assert_eq!(graph[NodeIndex::new(0)].attributes["label"], AttrValue::Quoted("<x>".to_string()));
+    assert_eq!(graph[NodeIndex::new(1)].attributes["label"], AttrValue::Html("<x>".to_string()));
+    let mut written = Vec::new();
+    graph.write(&mut written).unwrap();
+    let written = String::from_utf8(written).unwrap();
+    assert!(written.contains("label = \"<x>\"\n"), "{}", written);
+    assert!(written.contains("label = <x>\n"), "{}", written);
+    let reparsed = crate::graphviz::parse(&written);
+    assert_eq!(reparsed[NodeIndex::new(0)], graph[NodeIndex::new(0)]);
+    assert_eq!(reparsed[NodeIndex::new(1)], graph[NodeIndex::new(1)]);
+}
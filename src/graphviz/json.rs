@@ -0,0 +1,118 @@
+//! Export of a `DotGraph` in graphviz' own JSON format, as produced by `dot -Tjson0`.
+
+use std::collections::HashMap;
+
+use petgraph::visit::EdgeRef;
+use serde_json::{json, Map, Value};
+
+use crate::graphviz::{AttrValue, DotGraph, GraphType, NodeIndex, Subgraph};
+
+/// Inserts all attributes as string values into the object.
+fn insert_attributes<'a>(object: &mut Map<String, Value>, attributes: impl IntoIterator<Item = (&'a String, &'a AttrValue)>) {
+    for (key, value) in attributes {
+        object.insert(key.clone(), Value::String(value.to_string()));
+    }
+}
+
+impl DotGraph {
+    /// Converts this graph into graphviz' JSON format without layout information.
+    ///
+    /// Subgraphs and nodes are listed in `objects`, where subgraphs come first in pre-order,
+    /// followed by the nodes in the order of their node index.
+    /// Each object and edge has an `_gvid`, which is the index into `objects` and `edges`
+    /// respectively.
+    /// Global `node` and `edge` attributes are applied to every node and edge, unless the node
+    /// or edge overrides them.
+    pub fn to_gv_json(&self) -> String {
+        fn flatten<'a>(subgraphs: &'a [Subgraph], flat: &mut Vec<&'a Subgraph>) {
+            for subgraph in subgraphs {
+                flat.push(subgraph);
+                flatten(&subgraph.subgraphs, flat);
+            }
+        }
+        let mut subgraphs = Vec::new();
+        flatten(&self.subgraphs, &mut subgraphs);
+        let subgraph_ids: HashMap<_, _> = subgraphs.iter()
+            .enumerate()
+            .map(|(gvid, &subgraph)| (subgraph as *const Subgraph, gvid))
+            .collect();
+        let node_gvid = |node_idx: NodeIndex| subgraphs.len() + node_idx.index();
+
+        let mut objects = Vec::new();
+        for (gvid, subgraph) in subgraphs.iter().enumerate() {
+            let mut object = Map::new();
+            object.insert("_gvid".to_string(), json!(gvid));
+            object.insert("name".to_string(), json!(subgraph.id));
+            insert_attributes(&mut object, &subgraph.attributes);
+            let mut nodes: Vec<_> = subgraph.nodes.iter().map(|&node_idx| node_gvid(node_idx)).collect();
+            nodes.sort();
+            let edges: Vec<_> = self.edge_references()
+                .filter(|edge| subgraph.nodes.contains(&edge.source()) && subgraph.nodes.contains(&edge.target()))
+                .map(|edge| edge.id().index())
+                .collect();
+            if !subgraph.subgraphs.is_empty() {
+                let children: Vec<_> = subgraph.subgraphs.iter()
+                    .map(|child| subgraph_ids[&(child as *const Subgraph)])
+                    .collect();
+                object.insert("subgraphs".to_string(), json!(children));
+            }
+            object.insert("nodes".to_string(), json!(nodes));
+            object.insert("edges".to_string(), json!(edges));
+            objects.push(Value::Object(object));
+        }
+        for node_idx in self.node_indices() {
+            let node = &self[node_idx];
+            let mut object = Map::new();
+            object.insert("_gvid".to_string(), json!(node_gvid(node_idx)));
+            object.insert("name".to_string(), json!(node.id));
+            insert_attributes(&mut object, self.node_attributes.iter().chain(&node.attributes));
+            objects.push(Value::Object(object));
+        }
+        let edges: Vec<_> = self.edge_references()
+            .map(|edge| {
+                let mut object = Map::new();
+                object.insert("_gvid".to_string(), json!(edge.id().index()));
+                object.insert("tail".to_string(), json!(node_gvid(edge.source())));
+                object.insert("head".to_string(), json!(node_gvid(edge.target())));
+                insert_attributes(&mut object, self.edge_attributes.iter().chain(&edge.weight().attributes));
+                Value::Object(object)
+            }).collect();
+
+        let mut graph = Map::new();
+        graph.insert("name".to_string(), json!(self.id.clone().unwrap_or_default()));
+        graph.insert("directed".to_string(), json!(self._type == GraphType::Digraph));
+        graph.insert("strict".to_string(), json!(self.strict));
+        insert_attributes(&mut graph, &self.graph_attributes);
+        graph.insert("_subgraph_cnt".to_string(), json!(subgraphs.len()));
+        graph.insert("objects".to_string(), Value::Array(objects));
+        graph.insert("edges".to_string(), Value::Array(edges));
+        serde_json::to_string_pretty(&Value::Object(graph)).unwrap()
+    }
+}
+
+#[test]
+fn test_to_gv_json() {
+    let graph = crate::graphviz::parse(r#"digraph g {
+        rankdir=LR; node [shape=box]
+        subgraph cluster_a { a [label="A \"1\""]; a -> b }
+        b -> c [amount=2]
+    }"#);
+    let json: Value = serde_json::from_str(&graph.to_gv_json()).unwrap();
+    assert_eq!(json["name"], "g");
+    assert_eq!(json["directed"], true);
+    assert_eq!(json["rankdir"], "LR");
+    assert_eq!(json["_subgraph_cnt"], 1);
+    let objects = json["objects"].as_array().unwrap();
+    let edges = json["edges"].as_array().unwrap();
+    assert_eq!(objects.len(), 4);
+    assert_eq!(edges.len(), 2);
+    assert_eq!(objects[0]["name"], "cluster_a");
+    assert_eq!(objects[0]["nodes"], json!([1, 2]));
+    assert_eq!(objects[0]["edges"], json!([0]));
+    assert_eq!(objects[1]["name"], "a");
+    assert_eq!(objects[1]["label"], "A \"1\"");
+    assert_eq!(objects[3]["shape"], "box");
+    assert_eq!(edges[1]["tail"], 2);
+    assert_eq!(edges[1]["head"], 3);
+    assert_eq!(edges[1]["amount"], "2");
+}
@@ -0,0 +1,100 @@
+//! Checks for suspicious but syntactically valid constructs in dot files.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::graphviz::{statements, try_parse, ParseError, Statement};
+
+/// A suspicious construct found by [`lint`](fn.lint.html).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Lint {
+    /// An edge from a node to itself, i.e., an item depending on itself.
+    SelfLoop { id: String },
+    /// A node used in an edge without being declared by a node statement.
+    ///
+    /// Only reported if requested, as dot files commonly declare nodes implicitly by edges.
+    UndeclaredNode { id: String },
+    /// A node declared multiple times. Only the attributes of the first declaration are used.
+    DuplicateNode { id: String },
+    /// A node without any edges, i.e., an item which is neither produced nor consumed.
+    IsolatedNode { id: String },
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Lint::SelfLoop { id } => write!(f, "{:?} depends on itself", id),
+            Lint::UndeclaredNode { id } => write!(f, "{:?} is used in an edge, but never declared", id),
+            Lint::DuplicateNode { id } => write!(f, "{:?} is declared multiple times, ignoring all but the first declaration", id),
+            Lint::IsolatedNode { id } => write!(f, "{:?} is neither produced nor consumed", id),
+        }
+    }
+}
+
+/// Appends all statements to `flat`, replacing subgraphs by their statements.
+fn flatten<'a>(stmts: &'a [Statement], flat: &mut Vec<&'a Statement>) {
+    for stmt in stmts {
+        match stmt {
+            Statement::Subgraph(subgraph) => flatten(subgraph.statements(), flat),
+            stmt => flat.push(stmt),
+        }
+    }
+}
+
+/// Parses the dot language graph and returns all suspicious constructs in the order they appear.
+///
+/// Statements within subgraphs are checked as if they were top-level statements.
+/// Nodes used in edges without a node statement are only reported if `undeclared` is set, each
+/// of them once.
+pub fn lint(s: &str, undeclared: bool) -> Result<Vec<Lint>, ParseError> {
+    let all: Vec<_> = statements(s)?.collect();
+    let mut stmts = Vec::new();
+    flatten(&all, &mut stmts);
+    let mut lints = Vec::new();
+    let mut declared = HashSet::new();
+    for stmt in &stmts {
+        if let Statement::Node(node) = stmt {
+            if !declared.insert(&node.id) {
+                lints.push(Lint::DuplicateNode { id: node.id.clone() });
+            }
+        }
+    }
+    let mut reported = HashSet::new();
+    for stmt in &stmts {
+        if let Statement::Edge(edge) = stmt {
+            for pair in edge.nodes().windows(2) {
+                if pair[0] == pair[1] {
+                    lints.push(Lint::SelfLoop { id: pair[0].clone() });
+                }
+            }
+            for id in edge.nodes() {
+                if undeclared && !declared.contains(id) && reported.insert(id) {
+                    lints.push(Lint::UndeclaredNode { id: id.clone() });
+                }
+            }
+        }
+    }
+    let graph = try_parse(s)?;
+    lints.extend(graph.isolated_nodes().into_iter().map(|node| Lint::IsolatedNode { id: graph[node].id.clone() }));
+    Ok(lints)
+}
+
+#[test]
+fn test_lint() {
+    let lints = lint("digraph { a; b; a [color=red]; d; a -> b -> b; b -> c; c -> a }", true).unwrap();
+    assert_eq!(lints, vec![
+        Lint::DuplicateNode { id: "a".to_string() },
+        Lint::SelfLoop { id: "b".to_string() },
+        Lint::UndeclaredNode { id: "c".to_string() },
+        Lint::IsolatedNode { id: "d".to_string() },
+    ]);
+    assert_eq!(lint("digraph { a; b; a [color=red]; d; a -> b -> b; b -> c; c -> a }", false).unwrap(), vec![
+        Lint::DuplicateNode { id: "a".to_string() },
+        Lint::SelfLoop { id: "b".to_string() },
+        Lint::IsolatedNode { id: "d".to_string() },
+    ]);
+    assert!(lint("digraph { a; b; a -> b }", true).unwrap().is_empty());
+    assert!(lint("digraph { a -> b }", false).unwrap().is_empty());
+    assert_eq!(lint("digraph { subgraph cluster_x { a; b -> a } }", true).unwrap(), vec![Lint::UndeclaredNode { id: "b".to_string() }]);
+    assert!(lint("digraph { a -> }", false).is_err());
+}
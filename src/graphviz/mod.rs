@@ -6,16 +6,42 @@
 mod graph;
 mod builder;
 mod dot;
+mod analysis;
+mod lint;
+mod json;
+mod color;
 
-pub use dot::parse;
+pub use dot::{
+    parse,
+    try_parse,
+    parse_lenient,
+    parse_with_options,
+    ParseOptions,
+    EdgeChainMode,
+    DuplicateNodePolicy,
+    statements,
+    Statement,
+    EdgeInternal,
+    GlobalAttribute,
+    AttributeType,
+    ParseError,
+};
 pub use self::graph::{
     Graph,
     GraphIndex,
     NodeIndex,
     EdgeIndex,
     GraphType,
+    AttrValue,
     Node,
     Edge,
+    Subgraph,
     DotGraph,
+    WriteOptions,
+    IdCollisionError,
+    NotADag,
 };
-pub use self::builder::DotGraphBuilder;
+pub use self::builder::{DotGraphBuilder, BuildError};
+pub use self::analysis::GraphStats;
+pub use self::lint::{lint, Lint};
+pub use self::color::{ColorList, WeightedColor, set_color};
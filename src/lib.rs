@@ -0,0 +1,9 @@
+//! Library part of the factorio cluster finder.
+//!
+//! The `graphviz` module parses and writes the item dependency graph, while the `cluster` module
+//! contains the greedy search for clusters (subfactories) within that graph.
+//! The `factorio` module contains game specific helpers on top of both.
+
+pub mod graphviz;
+pub mod cluster;
+pub mod factorio;
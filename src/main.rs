@@ -1,102 +1,427 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::mpsc;
 
-use crate::graphviz::{DotGraph, NodeIndex};
+use factorio_cluster_finder::cluster::{self, ClusterOrder, ClusterParams, ClusterScore};
+use factorio_cluster_finder::factorio;
+use factorio_cluster_finder::graphviz::{self, DotGraph, NodeIndex};
 use petgraph::Direction;
+use notify::{RecursiveMode, Watcher};
 
-mod graphviz;
+/// Command line arguments of the driver.
+struct Args {
+    /// Path to the dot file containing the dependency graph.
+    path: String,
+    /// Recluster whenever the dot file changes.
+    watch: bool,
+    /// Exclude all items consumed by more than this many items from clustering.
+    exclude_fanin: Option<usize>,
+    /// Path to the toml file containing the clustering parameters.
+    config: String,
+    /// Seeds overriding the ones of the configuration file.
+    seeds: Vec<String>,
+    /// File with additional seeds, one item id per line.
+    seeds_file: Option<String>,
+    /// Maximum cluster size overriding the one of the configuration file.
+    max_cluster_size: Option<usize>,
+    /// Ignore the direction of edges when scoring and compare with the directed clustering.
+    undirected: bool,
+    /// Only cluster items with at least this `tier`.
+    tier_min: Option<f64>,
+    /// Only cluster items with at most this `tier`.
+    tier_max: Option<f64>,
+    /// Items the `bus` subcommand plans the main bus for.
+    targets: Vec<String>,
+    /// Items per second of each target of the `bus` subcommand.
+    rate: f64,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Args {
+        let mut parsed = Args {
+            path: "recipe.dot".to_string(),
+            watch: false,
+            exclude_fanin: None,
+            config: "cluster.toml".to_string(),
+            seeds: Vec::new(),
+            seeds_file: None,
+            max_cluster_size: None,
+            undirected: false,
+            tier_min: None,
+            tier_max: None,
+            targets: Vec::new(),
+            rate: 1.0,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--watch" => parsed.watch = true,
+                "--undirected" => parsed.undirected = true,
+                "--exclude-fanin" => {
+                    let threshold = args.next().expect("--exclude-fanin requires a threshold");
+                    parsed.exclude_fanin = Some(threshold.parse().expect("invalid --exclude-fanin threshold"));
+                }
+                "--config" => parsed.config = args.next().expect("--config requires a path"),
+                "--seed" => parsed.seeds.push(args.next().expect("--seed requires an item id")),
+                "--seeds-file" => parsed.seeds_file = Some(args.next().expect("--seeds-file requires a path")),
+                "--tier-min" => {
+                    let tier = args.next().expect("--tier-min requires a tier");
+                    parsed.tier_min = Some(tier.parse().expect("invalid --tier-min"));
+                }
+                "--tier-max" => {
+                    let tier = args.next().expect("--tier-max requires a tier");
+                    parsed.tier_max = Some(tier.parse().expect("invalid --tier-max"));
+                }
+                "--targets" => {
+                    let targets = args.next().expect("--targets requires a comma-separated list of item ids");
+                    parsed.targets.extend(targets.split(',').map(str::to_string));
+                }
+                "--rate" => {
+                    let rate = args.next().expect("--rate requires items per second");
+                    parsed.rate = rate.parse().expect("invalid --rate");
+                }
+                "--max-size" => {
+                    let max = args.next().expect("--max-size requires a size");
+                    parsed.max_cluster_size = Some(max.parse().expect("invalid --max-size"));
+                }
+                _ => parsed.path = arg,
+            }
+        }
+        parsed
+    }
+}
 
 fn main() {
-    env_logger::init();
-    let dot = std::fs::read_to_string("recipe.dot").unwrap();
-    let graph= graphviz::parse(&dot);
-
-    fn subgraph_neighbors_with_duplicates<'a>(subgraph: &'a HashSet<NodeIndex>, graph: &'a DotGraph) -> impl Iterator<Item = NodeIndex> + 'a {
-        subgraph.iter()
-            .copied()
-            .flat_map(move |node_idx| graph.neighbors_undirected(node_idx))
-            .filter(move |neighbor_idx| !subgraph.contains(neighbor_idx))
-    }
-    fn score(subgraph: &HashSet<NodeIndex>, graph: &DotGraph) -> (usize, usize) {
-        // number of dependencies, i.e., number of components required as input
-        let num_deps = subgraph.iter()
-            .copied()
-            .flat_map(|node_idx| graph.neighbors_directed(node_idx, Direction::Outgoing))
-            .filter(|neighbor_idx| !subgraph.contains(neighbor_idx))
-            .count();
-
-        // Number of outputs needed by other components,
-        // i.e. number of distinct output products required by other components.
-        // However, we shouldn't count sole inputs as output components (e.g. don't pipe through iron-plates).
-        let num_outputs = subgraph.iter()
-            .copied()
-            .filter(|node_idx|
-                graph.neighbors_directed(*node_idx, Direction::Incoming)
-                    .filter(|neighbor_idx| !subgraph.contains(neighbor_idx))
-                    .next().is_some()
-            ).filter(|node_ix|
-                graph.neighbors_directed(*node_ix, Direction::Outgoing)
-                    .filter(|neighbor_ix| subgraph.contains(neighbor_ix))
-                    .next().is_some()
-            ).count();
-        (num_deps, num_outputs)
-    }
-
-    // greedy
-    let mut node_set: HashSet<_> = graph.node_indices().collect();
-    let mut current_cluster = HashSet::new();
-
-    let search_for = &["sulfuric-acid"];
-
-    for name in search_for {
-        let item = graph.id_map()[*name];
-        current_cluster.insert(item);
-        node_set.remove(&item);
-    }
-    println!("starting with {} (score: {:?})", search_for.join(", "), score(&current_cluster, &graph));
-    loop {
-        let mut scores = Vec::new();
-        for node_idx in subgraph_neighbors_with_duplicates(&current_cluster, &graph) {
-            let mut cluster = current_cluster.clone();
-            cluster.insert(node_idx);
-            let (num_deps, num_outputs) = score(&cluster, &graph);
-            scores.push((node_idx, num_deps, num_outputs));
+    env_logger::init().unwrap();
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("validate") {
+        args.next();
+        let path = args.next().expect("validate requires a dot file");
+        let strict = args.next().as_deref() == Some("--strict");
+        std::process::exit(validate(&path, strict));
+    }
+    if args.peek().map(String::as_str) == Some("repl") {
+        args.next();
+        let args = Args::parse(args);
+        let dot = std::fs::read_to_string(&args.path).unwrap();
+        let graph = graphviz::parse(&dot);
+        let stdin = io::stdin();
+        repl(&graph, &load_params(&args), stdin.lock(), &mut io::stdout()).unwrap();
+        return;
+    }
+    if args.peek().map(String::as_str) == Some("stats") {
+        args.next();
+        let path = args.next().expect("stats requires a dot file");
+        let dot = std::fs::read_to_string(&path).unwrap();
+        print_stats(&graphviz::parse(&dot));
+        return;
+    }
+    if args.peek().map(String::as_str) == Some("bus") {
+        args.next();
+        bus(&Args::parse(args));
+        return;
+    }
+    let args = Args::parse(args);
+
+    let result = run(&args);
+    if let Err(e) = &result {
+        eprintln!("error: {}", e);
+    }
+    if args.watch {
+        watch(Path::new(&args.path), || recluster(&args)).unwrap();
+    } else if result.is_err() {
+        std::process::exit(1);
+    }
+}
+
+/// Clusters the changed dot file again, reporting errors instead of stopping, such that
+/// watching continues once the file is fixed.
+///
+/// Returns whether to keep watching, which is always the case.
+fn recluster(args: &Args) -> bool {
+    println!("{} changed, reclustering", args.path);
+    if let Err(e) = run(args) {
+        eprintln!("error: {}", e);
+    }
+    true
+}
+
+/// Checks the dot file for syntax errors and suspicious constructs without clustering it.
+///
+/// Returns the exit code, which is nonzero if the file couldn't be read or parsed.
+/// Suspicious constructs are only reported as warnings, undeclared nodes only if `strict` is set.
+fn validate(path: &str, strict: bool) -> i32 {
+    let dot = match std::fs::read_to_string(path) {
+        Ok(dot) => dot,
+        Err(e) => {
+            eprintln!("error: {}: {}", path, e);
+            return 1;
+        }
+    };
+    match graphviz::lint(&dot, strict) {
+        Ok(lints) => {
+            for lint in &lints {
+                eprintln!("warning: {}: {}", path, lint);
+            }
+            println!("{} is valid ({} warnings)", path, lints.len());
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}: {}", path, e);
+            1
         }
+    }
+}
 
-        let (current_deps, current_outputs) = score(&current_cluster, &graph);
+/// Prints a summary of the structure of the graph.
+fn print_stats(graph: &DotGraph) {
+    let stats = graph.stats();
+    println!("nodes: {}", stats.nodes);
+    println!("edges: {}", stats.edges);
+    println!("components: {}", stats.components);
+    println!("cycles: {}", stats.cycles);
+    println!("raw resources: {}", stats.raw_resources);
+    println!("final products: {}", stats.final_products);
+    println!("max depth: {}", stats.max_depth);
+    println!("average degree: {:.2}", stats.average_degree);
+}
 
-        let mut added_something = false;
+/// Items per second moved by one lane of a yellow belt.
+const LANE_CAPACITY: f64 = 7.5;
 
-        for (node_idx, num_deps, num_outputs) in scores.iter().cloned() {
-            if current_cluster.contains(&node_idx) {
-                continue;
+/// Clusters the graph and prints the number of main bus lanes required by each intermediate
+/// shared between clusters to produce the targets.
+fn bus(args: &Args) {
+    let dot = std::fs::read_to_string(&args.path).unwrap();
+    let graph = graphviz::parse(&dot);
+    let mut params = load_params(args);
+    remove_unknown_seeds(&graph, &mut params, &args.path);
+    let targets: Vec<_> = args.targets.iter()
+        .map(|id| *graph.id_map().get(id).unwrap_or_else(|| panic!("unknown target {:?}", id)))
+        .collect();
+    let clustering = cluster::greedy_cluster(&graph, &params);
+    for lane in factorio::bus_lanes(&clustering, &graph, &targets, args.rate, LANE_CAPACITY).expect("lane capacity is positive") {
+        println!("{} ({}/s): {} lanes", graph[lane.item].id, lane.throughput, lane.lanes);
+    }
+}
+
+/// Reads commands from `input` line by line until it ends or `quit` is read, writing the answer
+/// of each command to `output`.
+fn repl(graph: &DotGraph, params: &ClusterParams, input: impl BufRead, output: &mut impl Write) -> io::Result<()> {
+    const HELP: &str = "commands: deps <item>, consumers <item>, path <from> <to>, cluster <item>, score <item>..., frontier <item>..., quit";
+    let mut frontier_cache = cluster::FrontierCache::new();
+    let ids = |nodes: &mut dyn Iterator<Item = NodeIndex>| {
+        let mut ids: Vec<_> = nodes.map(|node_idx| graph[node_idx].id.as_str()).collect();
+        ids.sort();
+        ids.join(" ")
+    };
+    write!(output, "> ")?;
+    output.flush()?;
+    for line in input.lines() {
+        let line = line?;
+        let words: Vec<_> = line.split_whitespace().collect();
+        let nodes: Result<Vec<_>, _> = words.iter().skip(1)
+            .map(|&id| graph.id_map().get(id).copied().ok_or(id))
+            .collect();
+        match (words.first().copied(), nodes) {
+            (None, _) => {}
+            (Some("quit"), _) => return Ok(()),
+            (Some(_), Err(id)) => writeln!(output, "unknown item {:?}", id)?,
+            (Some("deps"), Ok(ref nodes)) if nodes.len() == 1 => {
+                writeln!(output, "{}", ids(&mut graph.neighbors_directed(nodes[0], Direction::Outgoing)))?
             }
-            let score = num_deps + num_outputs;
-            let current_score = current_deps + current_outputs;
-            if score <= current_score || (num_deps == current_deps && num_outputs > current_outputs) {
-                println!("    adding {} (score: {:?})", graph[node_idx].id, (num_deps, num_outputs));
-                current_cluster.insert(node_idx);
-                node_set.remove(&node_idx);
-                added_something = true;
+            (Some("consumers"), Ok(ref nodes)) if nodes.len() == 1 => {
+                writeln!(output, "{}", ids(&mut graph.neighbors_directed(nodes[0], Direction::Incoming)))?
             }
-        }
-        println!("    ---------");
-
-        if !added_something {
-            scores.sort_by_key(|(_, num_deps, num_outputs)| num_deps + num_outputs);
-            let lowest = scores[0];
-            for (node_idx, num_deps, num_outputs) in scores {
-                let score = num_deps + num_outputs;
-                let lowest_score = lowest.1 + lowest.2;
-                if score <= lowest_score {
-                    println!("    lowest would have been {} (score: {:?})", graph[node_idx].id, score);
+            (Some("path"), Ok(ref nodes)) if nodes.len() == 2 => {
+                match petgraph::algo::astar(&**graph, nodes[0], |node_idx| node_idx == nodes[1], |_| 1, |_| 0) {
+                    Some((_, path)) => {
+                        let path: Vec<_> = path.iter().map(|&node_idx| graph[node_idx].id.as_str()).collect();
+                        writeln!(output, "{}", path.join(" -> "))?
+                    }
+                    None => writeln!(output, "no path")?,
+                }
+            }
+            (Some("cluster"), Ok(ref nodes)) if nodes.len() == 1 => {
+                writeln!(output, "{}", ids(&mut cluster::cluster_around(nodes[0], graph, params).into_iter()))?
+            }
+            (Some("score"), Ok(nodes)) if !nodes.is_empty() => {
+                let score = cluster::score_with_params(&nodes.into_iter().collect(), graph, params);
+                writeln!(output, "{:?} (cost: {})", score, score.weighted(params))?
+            }
+            (Some("frontier"), Ok(nodes)) if !nodes.is_empty() => {
+                for &(node_idx, score) in frontier_cache.score_frontier(&nodes.into_iter().collect(), graph, params) {
+                    writeln!(output, "{} {:?} (cost: {})", graph[node_idx].id, score, score.weighted(params))?
                 }
             }
-            let next = *node_set.iter().next().unwrap();
-            node_set.remove(&next);
-            current_cluster.clear();
-            current_cluster.insert(next);
-            println!("starting with {} (score: {:?})", graph[next].id, score(&current_cluster, &graph));
+            (Some(_), Ok(_)) => writeln!(output, "{}", HELP)?,
+        }
+        write!(output, "> ")?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+/// Parses and clusters the dot file, printing a summary of all clusters.
+///
+/// Returns an error if the dot file can't be read or parsed.
+fn run(args: &Args) -> Result<(), String> {
+    let dot = std::fs::read_to_string(&args.path).map_err(|e| format!("can't read {}: {}", args.path, e))?;
+    let graph = graphviz::try_parse(&dot).map_err(|e| format!("can't parse {}: {}", args.path, e))?;
+
+    let mut params = load_params(args);
+    remove_unknown_seeds(&graph, &mut params, &args.path);
+    if let Some(threshold) = args.exclude_fanin {
+        for node_idx in cluster::high_fanin_items(&graph, threshold) {
+            println!("excluding {}", graph[node_idx].id);
+            params.excludes.push(graph[node_idx].id.clone());
+        }
+    }
+    let print_partial = |nodes: &HashSet<NodeIndex>, score: ClusterScore| {
+        println!("found cluster of {} items (score: {:?}, cost: {})", nodes.len(), score, score.weighted(&params));
+    };
+    let mut clustering = cluster::greedy_cluster_with_callback(&graph, &params, Some(&print_partial));
+    clustering.sort_by(ClusterOrder::Size);
+
+    for cluster in &clustering.clusters {
+        println!("{} (size: {}, score: {:?}, cost: {})", cluster.seed, cluster.nodes.len(), cluster.score, cluster.score.weighted(&params));
+        let mut ids: Vec<_> = cluster.nodes.iter().map(|&node_idx| &graph[node_idx].id).collect();
+        ids.sort();
+        for id in ids {
+            println!("    {}", id);
+        }
+    }
+
+    if params.undirected {
+        let directed = cluster::greedy_cluster(&graph, &ClusterParams { undirected: false, ..params });
+        let comparison = cluster::compare(&directed, &clustering, &graph);
+        println!("{:>10} {:>10} {:>10} {:>10}", "", "clusters", "cut", "peak cut");
+        for (name, metrics) in &[("directed", &comparison.left), ("undirected", &comparison.right)] {
+            println!("{:>10} {:>10} {:>10} {:>10}", name, metrics.num_clusters, metrics.cut_cost, metrics.peak_cut);
+        }
+        println!("stability: {:.3}", comparison.stability);
+    }
+    Ok(())
+}
+
+/// Reads the clustering parameters from the configuration file if it exists, overridden by the
+/// command line arguments.
+fn load_params(args: &Args) -> ClusterParams {
+    let mut params = match std::fs::read_to_string(&args.config) {
+        Ok(config) => toml::from_str(&config).unwrap_or_else(|e| panic!("invalid {}: {}", args.config, e)),
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => ClusterParams {
+            seeds: vec!["sulfuric-acid".to_string()],
+            ..ClusterParams::default()
+        },
+        Err(e) => panic!("can't read {}: {}", args.config, e),
+    };
+    let mut seeds = args.seeds.clone();
+    if let Some(path) = &args.seeds_file {
+        let file = std::fs::read_to_string(path).unwrap_or_else(|e| panic!("can't read {}: {}", path, e));
+        seeds.extend(read_seeds(&file));
+    }
+    if !seeds.is_empty() {
+        params.seeds = seeds;
+    }
+    if args.max_cluster_size.is_some() {
+        params.max_cluster_size = args.max_cluster_size;
+    }
+    if args.undirected {
+        params.undirected = true;
+    }
+    if args.tier_min.is_some() || args.tier_max.is_some() {
+        params.include = Some(cluster::tier_range(args.tier_min, args.tier_max));
+    }
+    params
+}
+
+/// Returns the item ids of a seeds file, which contains one id per line.
+///
+/// Blank lines and lines starting with `#` are ignored.
+fn read_seeds(file: &str) -> impl Iterator<Item = String> + '_ {
+    file.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+}
+
+/// Removes all seeds which aren't part of the graph, warning about each of them like
+/// `validate` does.
+fn remove_unknown_seeds(graph: &DotGraph, params: &mut ClusterParams, path: &str) {
+    params.seeds.retain(|id| {
+        let known = graph.id_map().contains_key(id);
+        if !known {
+            eprintln!("warning: {}: unknown seed {:?}", path, id);
+        }
+        known
+    });
+}
+
+/// Calls `on_change` whenever the file at `path` is modified, until it returns `false`.
+///
+/// The parent directory is watched instead of the file itself, such that editors replacing the
+/// file on save are handled as well.
+fn watch(path: &Path, mut on_change: impl FnMut() -> bool) -> notify::Result<()> {
+    let path = path.canonicalize()?;
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(path.parent().unwrap(), RecursiveMode::NonRecursive)?;
+    for event in rx {
+        let event = event?;
+        let relevant = (event.kind.is_modify() || event.kind.is_create())
+            && event.paths.contains(&path);
+        if relevant && !on_change() {
             break;
         }
     }
+    Ok(())
+}
+
+#[test]
+#[ignore]
+fn test_watch_reclusters_on_change() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    let path = std::env::temp_dir().join(format!("factorio-cluster-finder-watch-{}.dot", std::process::id()));
+    std::fs::write(&path, "digraph { a -> b }").unwrap();
+    let done = Arc::new(AtomicBool::new(false));
+    let writer = {
+        let path = path.clone();
+        let done = done.clone();
+        std::thread::spawn(move || {
+            while !done.load(Ordering::SeqCst) {
+                std::thread::sleep(Duration::from_millis(100));
+                std::fs::write(&path, "digraph { a -> b -> c }").unwrap();
+            }
+        })
+    };
+
+    let mut clusterings = Vec::new();
+    watch(&path, || {
+        let graph = graphviz::parse(&std::fs::read_to_string(&path).unwrap());
+        clusterings.push(cluster::greedy_cluster(&graph, &ClusterParams::default()));
+        false
+    }).unwrap();
+    done.store(true, Ordering::SeqCst);
+    writer.join().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(clusterings.len(), 1);
+}
+
+#[test]
+fn test_recluster_reports_errors() {
+    let path = std::env::temp_dir().join(format!("factorio-cluster-finder-recluster-{}.dot", std::process::id()));
+    let args = Args::parse(vec![path.to_str().unwrap().to_string()].into_iter());
+    // missing files and invalid dot are reported, but watching continues
+    assert!(run(&args).unwrap_err().starts_with("can't read"));
+    assert!(recluster(&args));
+    std::fs::write(&path, "digraph { a -> }").unwrap();
+    assert!(run(&args).unwrap_err().starts_with("can't parse"));
+    assert!(recluster(&args));
+    std::fs::write(&path, "digraph { a -> b }").unwrap();
+    let result = run(&args);
+    std::fs::remove_file(&path).unwrap();
+    assert!(result.is_ok());
 }
@@ -0,0 +1,32 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn test_bus() {
+    let path: PathBuf = std::env::temp_dir().join(format!("factorio-cluster-finder-bus-{}.dot", std::process::id()));
+    std::fs::write(&path, "digraph { red -> gear; gear -> plate [amount=2]; green -> gear; green -> plate }").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_factorio-cluster-finder"))
+        .arg("bus")
+        .arg(&path)
+        .arg("--config")
+        .arg(path.with_extension("toml"))
+        .arg("--seed")
+        .arg("red")
+        .arg("--seed")
+        .arg("green")
+        .arg("--max-size")
+        .arg("1")
+        .arg("--targets")
+        .arg("red,green")
+        .arg("--rate")
+        .arg("4")
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().collect::<Vec<_>>(), vec![
+        "gear (8/s): 2 lanes",
+        "plate (20/s): 3 lanes",
+    ]);
+}
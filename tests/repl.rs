@@ -0,0 +1,39 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+#[test]
+fn test_repl() {
+    let path: PathBuf = std::env::temp_dir().join(format!("factorio-cluster-finder-repl-{}.dot", std::process::id()));
+    std::fs::write(&path, "digraph { circuit -> cable -> copper; circuit -> iron; inserter -> circuit }").unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_factorio-cluster-finder"))
+        .arg("repl")
+        .arg(&path)
+        .arg("--config")
+        .arg(path.with_extension("toml"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap()
+        .write_all(b"deps circuit\nconsumers circuit\npath inserter copper\npath copper inserter\ncluster cable\nscore cable copper\nfrontier cable\ndeps steel\nhelp\nquit\ndeps iron\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().map(|line| line.strip_prefix("> ").unwrap_or(line)).collect();
+    assert_eq!(lines, vec![
+        "cable iron",
+        "inserter",
+        "inserter -> circuit -> cable -> copper",
+        "no path",
+        "cable copper",
+        "ClusterScore { num_deps: 0, num_outputs: 1 } (cost: 1)",
+        "circuit ClusterScore { num_deps: 2, num_outputs: 1 } (cost: 3)",
+        "copper ClusterScore { num_deps: 0, num_outputs: 1 } (cost: 1)",
+        "unknown item \"steel\"",
+        "commands: deps <item>, consumers <item>, path <from> <to>, cluster <item>, score <item>..., frontier <item>..., quit",
+        "",
+    ]);
+}
@@ -0,0 +1,31 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn test_seeds_file() {
+    let dir: PathBuf = std::env::temp_dir().join(format!("factorio-cluster-finder-seeds-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("recipe.dot"), "digraph { a -> b; c -> d; e }").unwrap();
+    std::fs::write(dir.join("seeds.txt"), "# clusters to start with\n\n  b\nsteel\n\n# d\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_factorio-cluster-finder"))
+        .arg(dir.join("recipe.dot"))
+        .arg("--config")
+        .arg(dir.join("cluster.toml"))
+        .arg("--seeds-file")
+        .arg(dir.join("seeds.txt"))
+        .arg("--seed")
+        .arg("e")
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let seeds: Vec<_> = stdout.lines()
+        .filter(|line| line.contains("(size: "))
+        .map(|line| line.split(' ').next().unwrap())
+        .collect();
+    // d isn't a seed, as it's commented out
+    assert_eq!(seeds, vec!["b", "c", "e"]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("unknown seed \"steel\""), "{}", stderr);
+}
@@ -0,0 +1,26 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn test_stats() {
+    let path: PathBuf = std::env::temp_dir().join(format!("factorio-cluster-finder-stats-{}.dot", std::process::id()));
+    std::fs::write(&path, "digraph { circuit -> cable -> copper; circuit -> iron; inserter -> circuit; inserter -> gear -> iron; a -> b -> a; c -> c; lonely }").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_factorio-cluster-finder"))
+        .arg("stats")
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().collect::<Vec<_>>(), vec![
+        "nodes: 10",
+        "edges: 9",
+        "components: 4",
+        "cycles: 2",
+        "raw resources: 3",
+        "final products: 2",
+        "max depth: 3",
+        "average degree: 1.80",
+    ]);
+}
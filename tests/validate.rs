@@ -0,0 +1,54 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Writes `dot` to a temporary file and runs the `validate` subcommand on it with `args`.
+fn validate(name: &str, dot: &str, args: &[&str]) -> Output {
+    let path: PathBuf = std::env::temp_dir().join(format!("factorio-cluster-finder-{}-{}.dot", name, std::process::id()));
+    std::fs::write(&path, dot).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_factorio-cluster-finder"))
+        .arg("validate")
+        .arg(&path)
+        .args(args)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    output
+}
+
+#[test]
+fn test_validate_good_file() {
+    let output = validate("good", "digraph { a; b; a -> b }", &[]);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn test_validate_warnings() {
+    let output = validate("warnings", "digraph { a; a -> a -> b }", &[]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("\"a\" depends on itself"), "{}", stderr);
+    assert!(!stderr.contains("never declared"), "{}", stderr);
+
+    let output = validate("strict", "digraph { a; a -> a -> b }", &["--strict"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("\"b\" is used in an edge, but never declared"), "{}", stderr);
+}
+
+#[test]
+fn test_validate_broken_file() {
+    let output = validate("broken", "digraph { a -> }", &[]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("error: "), "{}", stderr);
+}
+
+#[test]
+fn test_validate_missing_file() {
+    let output = Command::new(env!("CARGO_BIN_EXE_factorio-cluster-finder"))
+        .args(["validate", "does-not-exist.dot"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+}